@@ -1,9 +1,17 @@
+@public
 Feature: Asset information
 
-    Scenario: Asset pair information is retrieved from public API
+    Scenario Outline: Asset pair information is retrieved from public API
         Given request is not authenticated
-        When asset pair information is requested for XBT and USD
+        When asset pair information is requested for <base> and <quote>
         Then gets successful response as json
         And response contains error list as empty
-        And response only contains asset pair information XXBTZUSD
-        And asset pair information for XBT and USD as XXBTZUSD is as expected
\ No newline at end of file
+        And response only contains asset pair information <pair>
+        And asset pair information for <base> and <quote> as <pair> is as expected
+
+        Examples:
+            | base | quote | pair     |
+            | XBT  | USD   | XXBTZUSD |
+            | XBT  | EUR   | XXBTZEUR |
+            | ETH  | USD   | XETHZUSD |
+            | ETH  | EUR   | XETHZEUR |
\ No newline at end of file
@@ -1,3 +1,4 @@
+@private
 Feature: Current orders
 
     Scenario: Get all current open orders
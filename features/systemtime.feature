@@ -1,3 +1,4 @@
+@public @smoke
 Feature: System time
 
     Scenario: System time is retrieved from public API
@@ -0,0 +1,33 @@
+use somebdd::state::{diff_results, RunStats};
+use std::env;
+
+fn main() {
+    let params: Vec<String> = env::args().skip(1).collect();
+    let previous_path = match params.first() {
+        Some(p) => p.to_owned(),
+        _ => panic!("You must provide the previous result file as first parameter"),
+    };
+
+    let current_path = match params.get(1) {
+        Some(p) => p.to_owned(),
+        _ => panic!("You must provide the current result file as second parameter"),
+    };
+
+    let regression_ratio = match params.get(2) {
+        Some(r) => r
+            .parse::<f64>()
+            .expect("The regression ratio must be a number, e.g. 0.5"),
+        _ => 0.5,
+    };
+
+    let previous = RunStats::load(&previous_path)
+        .unwrap_or_else(|e| panic!("Impossible to read '{}': {}", previous_path, e));
+    let current = RunStats::load(&current_path)
+        .unwrap_or_else(|e| panic!("Impossible to read '{}': {}", current_path, e));
+
+    let diff = diff_results(&previous, &current, regression_ratio);
+    print!("{}", diff);
+
+    let code = if diff.has_new_failures() { 1 } else { 0 };
+    std::process::exit(code);
+}
@@ -0,0 +1,24 @@
+use somebdd::state::{merge_result_files, ConsoleWriter, OutputFormat, ReportWriter};
+use std::env;
+
+fn main() {
+    let params: Vec<String> = env::args().skip(1).collect();
+    let output = match params.first() {
+        Some(o) => o.to_owned(),
+        _ => panic!("You must provide the output file as first parameter"),
+    };
+
+    let inputs: Vec<String> = params.iter().skip(1).cloned().collect();
+    if inputs.is_empty() {
+        panic!("You must provide the result files to merge after the output file");
+    }
+
+    let stats = merge_result_files(&inputs)
+        .unwrap_or_else(|e| panic!("Impossible to merge result files: {}", e));
+    let (format, path) = OutputFormat::from_output(&output);
+    let _ = ConsoleWriter::default().write(&stats);
+    format
+        .writer(&path, false)
+        .write(&stats)
+        .unwrap_or_else(|e| panic!("Impossible to write the merged report: {}", e));
+}
@@ -1,16 +1,24 @@
 use cucumber_rust::event::{
-    CucumberEvent, FailureKind, FeatureEvent, ScenarioEvent, StepEvent, StepFailureKind,
+    CapturedOutput, CucumberEvent, FailureKind, FeatureEvent, PanicInfo, RuleEvent, ScenarioEvent,
+    StepEvent, StepFailureKind,
 };
-use cucumber_rust::{EventHandler, RunResult};
+use cucumber_rust::{gherkin, EventHandler, ExampleValues, RunResult};
 use serde::{Deserialize, Serialize};
+use std::io::Write;
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub mod state {
     use super::*;
 
+    pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
     #[derive(Default, Clone)]
     pub struct RunEventHandler {
         pub state: Arc<Mutex<EventHandlerState>>,
+        step_patterns: Vec<(gherkin::StepType, regex::Regex)>,
+        step_literals: Vec<(gherkin::StepType, String)>,
+        recorder: Option<api::ExchangeRecorder>,
     }
 
     #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -18,62 +26,476 @@ pub mod state {
         Passed,
         Failed,
         Skipped,
+        Undefined,
+        Pending,
+        Ambiguous,
+        TimedOut,
     }
 
     #[derive(Default, Clone, Serialize, Deserialize)]
     pub struct FeatureStats {
         pub name: String,
+        #[serde(default)]
+        pub keyword: String,
+        #[serde(default)]
+        pub line: usize,
+        pub path: Option<String>,
+        pub description: Option<String>,
+        #[serde(default)]
+        pub tags: Vec<String>,
+        /// The feature `Background`, whose steps are reported in every scenario.
+        #[serde(default)]
+        pub background: Option<BackgroundStats>,
         pub scenarios: Vec<ScenarioStats>,
+        #[serde(default)]
+        pub timing: Timing,
+    }
+
+    #[derive(Default, Clone, Serialize, Deserialize)]
+    pub struct BackgroundStats {
+        pub keyword: String,
+        pub line: usize,
     }
 
     #[derive(Default, Clone, Serialize, Deserialize)]
     pub struct StepStats {
         pub name: String,
         pub keyword: String,
+        #[serde(default)]
+        pub line: usize,
+        /// Whether the step comes from the feature `Background` rather than the scenario itself.
+        #[serde(default)]
+        pub background: bool,
         pub result: Option<StatResult>,
+        #[serde(default)]
+        pub timing: Timing,
+        pub error_message: Option<String>,
+        pub failure_location: Option<String>,
+        pub captured_stdout: Option<String>,
+        pub captured_stderr: Option<String>,
+        /// HTTP exchanges made while the step ran.
+        #[serde(default)]
+        pub attachments: Vec<api::HttpExchange>,
     }
 
     #[derive(Default, Clone, Serialize, Deserialize)]
     pub struct ScenarioStats {
         pub name: String,
+        #[serde(default)]
+        pub keyword: String,
+        #[serde(default)]
+        pub line: usize,
+        /// Tags of the scenario, including the ones inherited from its feature, rule and examples.
+        #[serde(default)]
+        pub tags: Vec<String>,
+        pub rule: Option<String>,
+        pub example_index: Option<usize>,
+        pub example_values: Option<String>,
         pub steps: Vec<StepStats>,
         pub result: Option<StatResult>,
+        #[serde(default)]
+        pub timing: Timing,
+    }
+
+    /// Wall clock timestamps, as microseconds since the UNIX epoch, of a feature, scenario or step.
+    #[derive(Default, Clone, Serialize, Deserialize)]
+    pub struct Timing {
+        pub start_micros: Option<u64>,
+        pub end_micros: Option<u64>,
+        pub duration_micros: Option<u64>,
     }
 
     #[derive(Default, Clone)]
     pub struct EventHandlerState {
         pub features: Vec<FeatureStats>,
+        /// When the whole run started and finished.
+        pub timing: Timing,
     }
 
     #[derive(Default, Clone, Serialize, Deserialize)]
     pub struct RunStats {
+        /// Version of the report layout, see `SCHEMA_VERSION`; reports written before it was
+        /// introduced have none and are read as version 0.
+        #[serde(default)]
+        pub schema_version: u32,
         pub total_featuress: u32,
         pub total_scenarios: u32,
         pub skipped_scenarios: u32,
         pub passed_scenarios: u32,
         pub failed_scenarios: u32,
+        #[serde(default)]
+        pub undefined_scenarios: u32,
+        #[serde(default)]
+        pub pending_scenarios: u32,
+        #[serde(default)]
+        pub ambiguous_scenarios: u32,
+        #[serde(default)]
+        pub timed_out_scenarios: u32,
         pub features: Vec<FeatureStats>,
+        #[serde(default)]
+        pub tags: Vec<TagStats>,
+        #[serde(default)]
+        pub metadata: RunMetadata,
+    }
+
+    /// Where and when a run happened, so that archived results can be traced back to a
+    /// deployment. It never holds the API keys.
+    #[derive(Default, Clone, Serialize, Deserialize)]
+    pub struct RunMetadata {
+        #[serde(default)]
+        pub timing: Timing,
+        pub api_host: Option<String>,
+        pub hostname: Option<String>,
+        #[serde(default)]
+        pub feature_paths: Vec<String>,
+        #[serde(default)]
+        pub tag_filters: Vec<String>,
+        /// Taken from the `GIT_COMMIT` environment variable.
+        pub git_commit: Option<String>,
+        /// Taken from the `BDD_LABEL_<NAME>` environment variables, keyed by lowercase name.
+        #[serde(default)]
+        pub labels: std::collections::BTreeMap<String, String>,
+    }
+
+    /// Scenario results aggregated by tag; failed includes every failure status and skipped
+    /// includes pending scenarios.
+    #[derive(Default, Clone, Serialize, Deserialize)]
+    pub struct TagStats {
+        pub name: String,
+        pub total_scenarios: u32,
+        pub passed_scenarios: u32,
+        pub failed_scenarios: u32,
+        pub skipped_scenarios: u32,
+        pub duration_micros: u64,
     }
 
+    /// Current version of the `RunStats` report layout, described by
+    /// `schema/run_stats.schema.json`.
+    pub const SCHEMA_VERSION: u32 = 4;
+
     impl RunStats {
-        pub fn new(result: &RunResult, state: &EventHandlerState) -> Self {
+        pub fn new(result: &RunResult, state: &EventHandlerState, metadata: RunMetadata) -> Self {
             Self {
                 total_featuress: result.features.total,
                 total_scenarios: result.scenarios.total,
-                skipped_scenarios: result.scenarios.skipped,
-                passed_scenarios: result.scenarios.passed,
-                failed_scenarios: result.scenarios.failed,
-                features: state.features.clone(),
+                metadata: RunMetadata {
+                    timing: state.timing.clone(),
+                    ..metadata
+                },
+                ..Self::from_features(state.features.clone())
+            }
+        }
+
+        /// Builds the stats with every counter computed from the given features.
+        pub fn from_features(features: Vec<FeatureStats>) -> Self {
+            let count = |result: StatResult| {
+                features
+                    .iter()
+                    .flat_map(|f| f.scenarios.iter())
+                    .filter(|s| s.result.clone().unwrap_or(StatResult::Skipped) == result)
+                    .count() as u32
+            };
+
+            Self {
+                schema_version: SCHEMA_VERSION,
+                total_featuress: features.len() as u32,
+                total_scenarios: features.iter().map(|f| f.scenarios.len() as u32).sum(),
+                skipped_scenarios: count(StatResult::Skipped),
+                passed_scenarios: count(StatResult::Passed),
+                failed_scenarios: count(StatResult::Failed),
+                undefined_scenarios: count(StatResult::Undefined),
+                pending_scenarios: count(StatResult::Pending),
+                ambiguous_scenarios: count(StatResult::Ambiguous),
+                timed_out_scenarios: count(StatResult::TimedOut),
+                tags: get_tag_stats(&features),
+                features,
+                metadata: RunMetadata::default(),
+            }
+        }
+
+        /// Combines the results of several runs, e.g. the shards of a split suite, into one.
+        ///
+        /// Features are matched by file and line, or by name for reports without source
+        /// locations, and scenarios by line and example row. When a
+        /// scenario shows up in more than one report, the run that finished it last wins, and
+        /// on equal end times the report given last wins. The metadata comes from the first
+        /// report, spanning the timing and feature paths of all of them.
+        pub fn merge(reports: &[RunStats]) -> Self {
+            let mut features: Vec<FeatureStats> = Vec::default();
+
+            reports
+                .iter()
+                .flat_map(|r| r.features.iter())
+                .for_each(|feature| {
+                    let merged = match features.iter_mut().find(|f| f.is_same_feature(feature)) {
+                        Some(merged) => merged,
+                        None => {
+                            features.push(FeatureStats {
+                                scenarios: Vec::default(),
+                                ..feature.clone()
+                            });
+                            features.last_mut().unwrap()
+                        }
+                    };
+
+                    merged.timing.merge(&feature.timing);
+                    feature.scenarios.iter().for_each(|scenario| {
+                        match merged
+                            .scenarios
+                            .iter_mut()
+                            .find(|s| s.is_same_scenario(scenario))
+                        {
+                            Some(existing)
+                                if existing.timing.end_micros > scenario.timing.end_micros => {}
+                            Some(existing) => *existing = scenario.clone(),
+                            None => merged.scenarios.push(scenario.clone()),
+                        }
+                    });
+                });
+
+            features.sort_by(|a, b| (&a.name, &a.path).cmp(&(&b.name, &b.path)));
+            features.iter_mut().for_each(|f| {
+                f.scenarios
+                    .sort_by_key(|s| (s.line, s.example_index.unwrap_or_default()))
+            });
+
+            let mut metadata = reports
+                .first()
+                .map(|r| r.metadata.clone())
+                .unwrap_or_default();
+            reports.iter().skip(1).for_each(|r| {
+                metadata.timing.merge(&r.metadata.timing);
+                r.metadata.feature_paths.iter().for_each(|p| {
+                    if !metadata.feature_paths.contains(p) {
+                        metadata.feature_paths.push(p.to_owned());
+                    }
+                });
+            });
+
+            Self {
+                metadata,
+                ..Self::from_features(features)
+            }
+        }
+
+        /// Reads a report written with any schema version up to `SCHEMA_VERSION`. Reports from
+        /// version 0 get the counters and tag stats that they lack computed from their features.
+        pub fn load(path: &str) -> Result<Self> {
+            let content = std::fs::read_to_string(path)?;
+            let value: serde_json::Value = serde_json::from_str(&content)?;
+            let version = value
+                .get("schema_version")
+                .and_then(|v| v.as_u64())
+                .unwrap_or_default();
+            if version > SCHEMA_VERSION as u64 {
+                return Err(format!(
+                    "Unsupported schema version {}, the newest supported is {}",
+                    version, SCHEMA_VERSION
+                )
+                .into());
+            }
+
+            let stats: Self = serde_json::from_value(value)?;
+            match stats.schema_version {
+                0 => Ok(Self {
+                    total_featuress: stats.total_featuress,
+                    total_scenarios: stats.total_scenarios,
+                    ..Self::from_features(stats.features)
+                }),
+                _ => Ok(Self {
+                    schema_version: SCHEMA_VERSION,
+                    ..stats
+                }),
+            }
+        }
+
+        /// Whether any scenario ended in a status that should fail the run.
+        pub fn failed(&self) -> bool {
+            self.failed_scenarios > 0
+                || self.undefined_scenarios > 0
+                || self.ambiguous_scenarios > 0
+                || self.timed_out_scenarios > 0
+        }
+    }
+
+    impl RunMetadata {
+        /// Collects the metadata known before the run; the timing is filled by `RunStats::new`.
+        pub fn new(
+            api_context: &crate::api::ApiContext,
+            feature_paths: &[&str],
+            tag_filters: &[String],
+        ) -> Self {
+            Self {
+                timing: Timing::default(),
+                api_host: Some(api_context.get_api_host().to_string()),
+                hostname: get_hostname(),
+                feature_paths: feature_paths.iter().map(|p| p.to_string()).collect(),
+                tag_filters: tag_filters.to_vec(),
+                git_commit: std::env::var("GIT_COMMIT").ok().filter(|c| !c.is_empty()),
+                labels: std::env::vars()
+                    .filter(|(key, _)| key.starts_with(LABEL_PREFIX))
+                    .map(|(key, value)| (key[LABEL_PREFIX.len()..].to_lowercase(), value))
+                    .collect(),
+            }
+        }
+    }
+
+    const LABEL_PREFIX: &str = "BDD_LABEL_";
+
+    fn get_hostname() -> Option<String> {
+        std::env::var("HOSTNAME")
+            .or_else(|_| std::env::var("COMPUTERNAME"))
+            .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+            .map(|h| h.trim().to_string())
+            .ok()
+            .filter(|h| !h.is_empty())
+    }
+
+    fn get_tag_stats(features: &[FeatureStats]) -> Vec<TagStats> {
+        let mut tags: Vec<TagStats> = Vec::default();
+
+        features
+            .iter()
+            .flat_map(|f| f.scenarios.iter())
+            .for_each(|scenario| {
+                let result = scenario.result.clone().unwrap_or(StatResult::Skipped);
+                scenario.tags.iter().for_each(|tag| {
+                    let index = match tags.iter().position(|t| t.name == *tag) {
+                        Some(index) => index,
+                        None => {
+                            tags.push(TagStats {
+                                name: tag.to_owned(),
+                                ..TagStats::default()
+                            });
+                            tags.len() - 1
+                        }
+                    };
+
+                    let tag_stats = &mut tags[index];
+                    tag_stats.total_scenarios += 1;
+                    tag_stats.duration_micros +=
+                        scenario.timing.duration_micros.unwrap_or_default();
+                    match result {
+                        StatResult::Passed => tag_stats.passed_scenarios += 1,
+                        StatResult::Skipped | StatResult::Pending => {
+                            tag_stats.skipped_scenarios += 1
+                        }
+                        _ => tag_stats.failed_scenarios += 1,
+                    }
+                });
+            });
+
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        tags
+    }
+
+    impl std::fmt::Display for TagStats {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "Tag: '{}'; Total: {}; Passed: {}; Failed: {}; Skipped: {}; Duration: {:.3} ms",
+                self.name,
+                self.total_scenarios,
+                self.passed_scenarios,
+                self.failed_scenarios,
+                self.skipped_scenarios,
+                self.duration_micros as f64 / 1000.0
+            )
+        }
+    }
+
+    impl StatResult {
+        /// Whether the status stands for a broken scenario or step, as opposed to one that
+        /// passed or was deliberately not run.
+        pub fn is_failure(&self) -> bool {
+            matches!(
+                self,
+                Self::Failed | Self::Undefined | Self::Ambiguous | Self::TimedOut
+            )
+        }
+
+        fn get_severity(&self) -> u8 {
+            match self {
+                Self::Passed => 0,
+                Self::Skipped => 1,
+                Self::Pending => 2,
+                Self::Undefined => 3,
+                Self::Ambiguous => 4,
+                Self::TimedOut => 5,
+                Self::Failed => 6,
+            }
+        }
+    }
+
+    impl Timing {
+        pub(crate) fn started() -> Self {
+            Self {
+                start_micros: Some(get_now_micros()),
+                end_micros: None,
+                duration_micros: None,
+            }
+        }
+
+        /// Widens the timing to also cover `other`.
+        fn merge(&mut self, other: &Timing) {
+            self.start_micros = match (self.start_micros, other.start_micros) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            self.end_micros = self.end_micros.max(other.end_micros);
+            self.duration_micros = match (self.start_micros, self.end_micros) {
+                (Some(start), Some(end)) => Some(end.saturating_sub(start)),
+                _ => None,
+            };
+        }
+
+        fn is_finished(&self) -> bool {
+            self.end_micros.is_some()
+        }
+
+        pub(crate) fn finish(&mut self) {
+            let end = get_now_micros();
+            self.end_micros = Some(end);
+            self.duration_micros = self.start_micros.map(|start| end.saturating_sub(start));
+        }
+
+        pub fn get_duration_millis(&self) -> Option<f64> {
+            self.duration_micros.map(|d| d as f64 / 1000.0)
+        }
+    }
+
+    impl std::fmt::Display for Timing {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self.get_duration_millis() {
+                Some(duration) => write!(f, "; Duration: {:.3} ms", duration),
+                None => Ok(()),
             }
         }
     }
 
+    /// Spectral decorates its assertion messages with terminal escape codes.
+    fn strip_ansi(value: &str) -> String {
+        regex::Regex::new("\x1b\\[[0-9;]*m")
+            .unwrap()
+            .replace_all(value, "")
+            .into_owned()
+    }
+
+    fn get_now_micros() -> u64 {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        timestamp.as_micros() as u64
+    }
+
     impl std::fmt::Display for StatResult {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             let output = match self {
                 &Self::Failed => "Failed",
                 &Self::Passed => "Passed",
                 &Self::Skipped => "Skipped",
+                &Self::Undefined => "Undefined",
+                &Self::Pending => "Pending",
+                &Self::Ambiguous => "Ambiguous",
+                &Self::TimedOut => "TimedOut",
             };
 
             write!(f, "{}", output)
@@ -84,10 +506,16 @@ pub mod state {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(
                 f,
-                "Step: '{} {}'; Status: '{}'",
+                "{}: '{} {}'; Status: '{}'{}",
+                if self.background {
+                    "Background step"
+                } else {
+                    "Step"
+                },
                 self.keyword,
                 self.name,
-                self.result.clone().unwrap_or(state::StatResult::Skipped)
+                self.result.clone().unwrap_or(state::StatResult::Skipped),
+                self.timing
             )
         }
     }
@@ -96,9 +524,10 @@ pub mod state {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(
                 f,
-                "Scenario: '{}'; Status: '{}'",
-                self.name,
-                self.result.clone().unwrap_or(state::StatResult::Skipped)
+                "Scenario: '{}'; Status: '{}'{}",
+                self.get_display_name(),
+                self.result.clone().unwrap_or(state::StatResult::Skipped),
+                self.timing
             )
         }
     }
@@ -107,23 +536,51 @@ pub mod state {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(
                 f,
-                "Feature: '{}'; Status: '{}'",
+                "Feature: '{}'; Status: '{}'{}",
                 self.name,
-                self.get_result().unwrap_or(state::StatResult::Skipped)
+                self.get_result().unwrap_or(state::StatResult::Skipped),
+                self.timing
             )
         }
     }
 
     impl FeatureStats {
-        fn new(name: String) -> Self {
+        fn new(feature: &gherkin::Feature) -> Self {
             Self {
-                name,
+                name: feature.name.to_owned(),
+                keyword: feature.keyword.to_owned(),
+                line: feature.position.line,
+                path: get_feature_path(feature),
+                description: feature.description.clone(),
+                tags: get_tags(&feature.tags),
+                background: feature.background.as_ref().map(|b| BackgroundStats {
+                    keyword: b.keyword.to_owned(),
+                    line: b.position.line,
+                }),
                 scenarios: Vec::default(),
+                timing: Timing::started(),
+            }
+        }
+
+        /// Returns `path:line` for an element of this feature, so reports can link to the source.
+        pub fn get_location(&self, line: usize) -> String {
+            match &self.path {
+                Some(path) => format!("{}:{}", path, line),
+                None => format!("{}:{}", self.name, line),
             }
         }
 
-        fn get_scenario(&mut self, scenario_name: String) -> Option<&mut ScenarioStats> {
-            self.scenarios.iter_mut().find(|s| s.name == scenario_name)
+        fn get_scenario(&mut self, line: usize) -> Option<&mut ScenarioStats> {
+            self.scenarios.iter_mut().rev().find(|s| s.line == line)
+        }
+
+        /// Whether both stats come from the same feature of different runs: the same file and
+        /// line, or the same name for reports without source locations.
+        fn is_same_feature(&self, other: &FeatureStats) -> bool {
+            match (&self.path, &other.path) {
+                (Some(path), Some(other_path)) => path == other_path && self.line == other.line,
+                _ => self.name == other.name,
+            }
         }
 
         pub fn get_result(&self) -> Option<StatResult> {
@@ -137,300 +594,4402 @@ pub mod state {
                         .collect();
                     if all_stats.iter().all(|s| *s == StatResult::Passed) {
                         Some(StatResult::Passed)
-                    } else if all_stats.iter().any(|s| *s == StatResult::Failed) {
-                        Some(StatResult::Failed)
                     } else {
-                        Some(StatResult::Skipped)
+                        all_stats
+                            .into_iter()
+                            .filter(|s| *s != StatResult::Passed)
+                            .max_by_key(|s| s.get_severity())
                     }
                 }
             }
         }
     }
 
+    fn get_tags(tags: &[String]) -> Vec<String> {
+        tags.iter().map(|t| format!("@{}", t)).collect()
+    }
+
+    fn get_feature_path(feature: &gherkin::Feature) -> Option<String> {
+        feature
+            .path
+            .as_ref()
+            .map(|p| p.to_string_lossy().into_owned())
+    }
+
     impl ScenarioStats {
-        fn new(name: String) -> Self {
+        /// Whether both stats come from the same scenario, or outline example row, of different
+        /// runs: the same line, or the same name for reports without source locations.
+        fn is_same_scenario(&self, other: &ScenarioStats) -> bool {
+            let same_source = match self.line > 0 && other.line > 0 {
+                true => self.line == other.line,
+                false => self.name == other.name,
+            };
+            same_source && self.example_index == other.example_index
+        }
+
+        fn new(
+            feature: &gherkin::Feature,
+            rule: Option<&gherkin::Rule>,
+            scenario: &gherkin::Scenario,
+            example_index: Option<usize>,
+            examples: &ExampleValues,
+        ) -> Self {
+            let mut tags: Vec<String> = Vec::default();
+            get_tags(&feature.tags)
+                .into_iter()
+                .chain(rule.map(|r| get_tags(&r.tags)).unwrap_or_default())
+                .chain(get_tags(&scenario.tags))
+                .chain(
+                    scenario
+                        .examples
+                        .as_ref()
+                        .map(|e| get_tags(&e.tags))
+                        .unwrap_or_default(),
+                )
+                .for_each(|tag| {
+                    if !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                });
+
+            let background_steps = feature
+                .background
+                .iter()
+                .flat_map(|b| b.steps.iter())
+                .map(|st| StepStats::new(st, st.value.to_owned(), true));
+            let scenario_steps = scenario
+                .steps
+                .iter()
+                .map(|st| StepStats::new(st, examples.insert_values(&st.value), false));
+
             Self {
-                name,
-                steps: Vec::default(),
+                name: scenario.name.to_owned(),
+                keyword: scenario.keyword.to_owned(),
+                line: scenario.position.line,
+                tags,
+                rule: rule.map(|r| r.name.to_owned()),
+                example_index,
+                example_values: match examples.is_empty() {
+                    true => None,
+                    false => Some(examples.to_string()),
+                },
+                steps: background_steps.chain(scenario_steps).collect(),
                 result: None,
+                timing: Timing::started(),
             }
         }
 
-        fn get_step(&mut self, step_keyword: String, step_name: String) -> Option<&mut StepStats> {
-            self.steps
+        fn get_step(&mut self, line: usize) -> Option<&mut StepStats> {
+            self.steps.iter_mut().rev().find(|s| s.line == line)
+        }
+
+        /// Narrows down the result reported by cucumber using the results of its steps; steps
+        /// that never started are marked as skipped.
+        fn finish(&mut self, result: StatResult) {
+            let step_results: Vec<StatResult> = self
+                .steps
                 .iter_mut()
-                .find(|s| s.name == step_name && s.keyword == step_keyword)
+                .map(|st| {
+                    if st.result.is_none() {
+                        st.result = Some(StatResult::Skipped);
+                    }
+                    st.result.clone().unwrap()
+                })
+                .collect();
+            let has_step = |result: StatResult| step_results.contains(&result);
+
+            self.result = Some(match result {
+                StatResult::Passed | StatResult::Skipped if has_step(StatResult::Ambiguous) => {
+                    StatResult::Ambiguous
+                }
+                StatResult::Skipped if has_step(StatResult::Undefined) => StatResult::Undefined,
+                StatResult::Skipped if has_step(StatResult::Pending) => StatResult::Pending,
+                _ => result,
+            });
+            self.timing.finish();
+        }
+
+        /// Returns the scenario name followed by the example values of its row, if any.
+        pub fn get_display_name(&self) -> String {
+            match &self.example_values {
+                Some(values) => format!("{} ({})", self.name, values),
+                None => self.name.to_owned(),
+            }
         }
     }
 
     impl StepStats {
-        fn new(name: String, keyword: String) -> Self {
+        fn new(step: &gherkin::Step, name: String, background: bool) -> Self {
             Self {
                 name,
-                keyword,
+                keyword: step.keyword.to_owned(),
+                line: step.position.line,
+                background,
                 result: None,
+                timing: Timing::default(),
+                error_message: None,
+                failure_location: None,
+                captured_stdout: None,
+                captured_stderr: None,
+                attachments: Vec::default(),
             }
         }
+
+        fn set_output(&mut self, output: &CapturedOutput) {
+            let get_output = |value: &String| match value.trim() {
+                "" => None,
+                _ => Some(strip_ansi(value)),
+            };
+
+            self.captured_stdout = get_output(&output.out);
+            self.captured_stderr = get_output(&output.err);
+        }
+
+        fn set_panic(&mut self, output: &CapturedOutput, info: &PanicInfo) {
+            self.set_output(output);
+            self.error_message = Some(strip_ansi(&info.payload).trim().to_string());
+            self.failure_location = Some(format!(
+                "{}:{}:{}",
+                info.location.file, info.location.line, info.location.column
+            ));
+        }
+
+        fn set_timeout(&mut self) {
+            self.error_message = Some(String::from("Step timed out"));
+        }
+
+        fn set_undefined(&mut self) {
+            self.error_message = Some(String::from("No step definition matches this step"));
+        }
+
+        fn set_ambiguous(&mut self, patterns: Vec<String>) {
+            self.result = Some(StatResult::Ambiguous);
+            self.error_message = Some(format!(
+                "Step matches {} definitions: {}",
+                patterns.len(),
+                patterns.join(", ")
+            ));
+        }
     }
 
     impl EventHandlerState {
-        fn add_feature(&mut self, feature_name: String) {
-            self.features.push(FeatureStats::new(feature_name));
+        fn add_feature(&mut self, feature: &gherkin::Feature) {
+            self.features.push(FeatureStats::new(feature));
         }
 
-        fn add_scenario(&mut self, feature_name: String, scenario_name: String) {
-            self.get_feature(feature_name.to_owned())
-                .expect(
-                    format!("Feature '{}' not found while adding scenario", feature_name).as_str(),
-                )
-                .scenarios
-                .push(ScenarioStats::new(scenario_name));
+        fn add_scenario(
+            &mut self,
+            feature: &gherkin::Feature,
+            rule: Option<&gherkin::Rule>,
+            scenario: &gherkin::Scenario,
+            examples: &ExampleValues,
+        ) {
+            let feature_stats = self.get_feature(feature);
+            let example_index = match examples.is_empty() {
+                true => None,
+                false => Some(
+                    feature_stats
+                        .scenarios
+                        .iter()
+                        .filter(|s| s.line == scenario.position.line)
+                        .count(),
+                ),
+            };
+
+            feature_stats.scenarios.push(ScenarioStats::new(
+                feature,
+                rule,
+                scenario,
+                example_index,
+                examples,
+            ));
         }
 
-        fn add_step(
+        fn start_step(
             &mut self,
-            feature_name: String,
-            scenario_name: String,
-            step_name: String,
-            step_keyword: String,
-        ) {
-            self.get_feature(feature_name.to_owned())
-                .expect(format!("Feature '{}' not found while adding step", feature_name).as_str())
-                .get_scenario(scenario_name.to_owned())
-                .expect(
-                    format!("Scenario '{}' not found  while adding step", scenario_name).as_str(),
-                )
+            feature: &gherkin::Feature,
+            scenario: &gherkin::Scenario,
+            step: &gherkin::Step,
+        ) -> &mut StepStats {
+            let scenario = self.get_scenario(feature, scenario);
+            let index = match scenario
                 .steps
-                .push(StepStats::new(step_name, step_keyword));
+                .iter()
+                .position(|s| s.line == step.position.line && !s.timing.is_finished())
+            {
+                Some(index) => index,
+                None => {
+                    scenario
+                        .steps
+                        .push(StepStats::new(step, step.value.to_owned(), false));
+                    scenario.steps.len() - 1
+                }
+            };
+
+            let step = &mut scenario.steps[index];
+            step.timing = Timing::started();
+            step
         }
 
         fn set_scenario_result(
             &mut self,
-            feature_name: String,
-            scenario_name: String,
+            feature: &gherkin::Feature,
+            scenario: &gherkin::Scenario,
             result: StatResult,
         ) {
-            self.get_feature(feature_name.to_owned())
-                .expect(
-                    format!(
-                        "Feature '{}' not found while setting result to scenario",
-                        feature_name
-                    )
-                    .as_str(),
-                )
-                .get_scenario(scenario_name.to_owned())
-                .expect(
-                    format!(
-                        "Scenario '{}' not found while setting result to scenario",
-                        scenario_name
-                    )
-                    .as_str(),
-                )
-                .result = Some(result);
+            self.get_scenario(feature, scenario).finish(result);
         }
 
         fn set_step_result(
             &mut self,
-            feature_name: String,
-            scenario_name: String,
-            step_name: String,
-            step_keyword: String,
+            feature: &gherkin::Feature,
+            scenario: &gherkin::Scenario,
+            step: &gherkin::Step,
             result: StatResult,
-        ) {
-            self.get_feature(feature_name.to_owned())
-                .expect(
-                    format!(
-                        "Feature '{}' not found while setting result to step",
-                        feature_name
-                    )
-                    .as_str(),
-                )
-                .get_scenario(scenario_name.to_owned())
-                .expect(
-                    format!(
-                        "Scenario '{}' not found while setting result to step",
-                        scenario_name
-                    )
-                    .as_str(),
-                )
-                .get_step(step_keyword.to_owned(), step_name.to_owned())
-                .expect(
-                    format!(
-                        "Step '{} {}' not found while setting result to step",
-                        step_keyword, step_name
+        ) -> &mut StepStats {
+            let step = self
+                .get_scenario(feature, scenario)
+                .get_step(step.position.line)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Step '{} {}' at line {} not found while setting result to step",
+                        step.keyword, step.value, step.position.line
                     )
-                    .as_str(),
-                )
-                .result = Some(result);
+                });
+            if step.result != Some(StatResult::Ambiguous) {
+                step.result = Some(result);
+            }
+            step.timing.finish();
+            step
+        }
+
+        fn finish_feature(&mut self, feature: &gherkin::Feature) {
+            self.get_feature(feature).timing.finish();
+        }
+
+        fn get_feature(&mut self, feature: &gherkin::Feature) -> &mut FeatureStats {
+            let path = get_feature_path(feature);
+            self.features
+                .iter_mut()
+                .find(|f| f.path == path && f.line == feature.position.line)
+                .unwrap_or_else(|| panic!("Feature '{}' not found", feature.name))
+        }
+
+        /// Attaches the HTTP exchanges made while a step ran to that step.
+        fn add_attachments(
+            &mut self,
+            feature: &gherkin::Feature,
+            scenario: &gherkin::Scenario,
+            step: &gherkin::Step,
+            attachments: Vec<api::HttpExchange>,
+        ) {
+            if let Some(step) = self
+                .get_scenario(feature, scenario)
+                .get_step(step.position.line)
+            {
+                step.attachments.extend(attachments);
+            }
         }
 
-        fn get_feature(&mut self, feature_name: String) -> Option<&mut FeatureStats> {
-            self.features.iter_mut().find(|f| f.name == feature_name)
+        /// Scenario Outlines report one entry per example row, so the lookup goes to the row
+        /// that started last.
+        fn get_scenario(
+            &mut self,
+            feature: &gherkin::Feature,
+            scenario: &gherkin::Scenario,
+        ) -> &mut ScenarioStats {
+            self.get_feature(feature)
+                .get_scenario(scenario.position.line)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Scenario '{}' at line {} not found",
+                        scenario.name, scenario.position.line
+                    )
+                })
         }
     }
 
-    impl EventHandler for RunEventHandler {
-        fn handle_event(&mut self, event: &CucumberEvent) {
-            let mut state = self.state.lock().unwrap();
+    impl RunEventHandler {
+        /// Registers the regex step definitions so that steps matching more than one of them
+        /// are reported as ambiguous. cucumber_rust doesn't expose its step definitions, so
+        /// every regex step must be registered here, or it's never checked.
+        pub fn with_step_patterns(patterns: &[(gherkin::StepType, &str)]) -> Self {
+            Self {
+                state: Arc::default(),
+                step_patterns: patterns
+                    .iter()
+                    .map(|(ty, pattern)| (*ty, regex::Regex::new(pattern).unwrap()))
+                    .collect(),
+                step_literals: Vec::default(),
+                recorder: None,
+            }
+        }
 
-            match event {
-                CucumberEvent::Feature(
-                    _feature,
-                    FeatureEvent::Scenario(_scenario, ScenarioEvent::Failed(FailureKind::Panic)),
-                ) => state.set_scenario_result(
-                    _feature.name.to_owned(),
-                    _scenario.name.to_owned(),
-                    StatResult::Failed,
-                ),
+        /// Registers the literal step definitions. cucumber_rust resolves a step by its
+        /// literal definition before trying any regex, so such a step is never ambiguous.
+        pub fn with_step_literals(mut self, literals: &[(gherkin::StepType, &str)]) -> Self {
+            self.step_literals = literals
+                .iter()
+                .map(|(ty, literal)| (*ty, literal.to_string()))
+                .collect();
+            self
+        }
 
-                CucumberEvent::Feature(
-                    _feature,
-                    FeatureEvent::Scenario(_scenario, ScenarioEvent::Passed),
-                ) => state.set_scenario_result(
-                    _feature.name.to_owned(),
-                    _scenario.name.to_owned(),
-                    StatResult::Passed,
-                ),
+        /// Attaches the HTTP exchanges recorded while a step runs to that step.
+        pub fn with_exchange_recorder(mut self, recorder: api::ExchangeRecorder) -> Self {
+            self.recorder = Some(recorder);
+            self
+        }
 
-                CucumberEvent::Feature(
-                    ref _feature,
-                    FeatureEvent::Scenario(ref _scenario, ScenarioEvent::Skipped),
-                ) => state.set_scenario_result(
-                    _feature.name.to_owned(),
-                    _scenario.name.to_owned(),
-                    StatResult::Skipped,
-                ),
+        fn get_matching_patterns(&self, step: &gherkin::Step) -> Vec<String> {
+            let literal_match = self
+                .step_literals
+                .iter()
+                .any(|(ty, literal)| *ty == step.ty && *literal == step.value);
+            if literal_match {
+                return Vec::default();
+            }
 
-                CucumberEvent::Feature(
-                    _feature,
-                    FeatureEvent::Scenario(
-                        _scenario,
-                        ScenarioEvent::Step(_step, StepEvent::Failed(StepFailureKind::Panic(_, _))),
-                    ),
-                ) => {
-                    state.set_step_result(
-                        _feature.name.to_owned(),
-                        _scenario.name.to_owned(),
-                        _step.value.to_owned(),
-                        _step.keyword.to_owned(),
-                        StatResult::Failed,
-                    );
-                }
+            self.step_patterns
+                .iter()
+                .filter(|(ty, regex)| *ty == step.ty && regex.is_match(&step.value))
+                .map(|(_, regex)| format!("'{}'", regex.as_str()))
+                .collect()
+        }
+    }
 
-                CucumberEvent::Feature(
-                    _feature,
-                    FeatureEvent::Scenario(
-                        _scenario,
-                        ScenarioEvent::Step(_step, StepEvent::Failed(StepFailureKind::TimedOut)),
-                    ),
-                ) => state.set_step_result(
-                    _feature.name.to_owned(),
-                    _scenario.name.to_owned(),
-                    _step.value.to_owned(),
-                    _step.keyword.to_owned(),
-                    StatResult::Failed,
-                ),
+    impl RunEventHandler {
+        fn handle_scenario_event(
+            &self,
+            state: &mut EventHandlerState,
+            feature: &gherkin::Feature,
+            rule: Option<&gherkin::Rule>,
+            scenario: &gherkin::Scenario,
+            event: &ScenarioEvent,
+        ) {
+            match event {
+                ScenarioEvent::Starting(examples) => {
+                    state.add_scenario(feature, rule, scenario, examples)
+                }
 
-                CucumberEvent::Feature(
-                    _feature,
-                    FeatureEvent::Scenario(
-                        _scenario,
-                        ScenarioEvent::Step(_step, StepEvent::Passed(_)),
-                    ),
-                ) => state.set_step_result(
-                    _feature.name.to_owned(),
-                    _scenario.name.to_owned(),
-                    _step.value.to_owned(),
-                    _step.keyword.to_owned(),
-                    StatResult::Passed,
-                ),
+                ScenarioEvent::Background(step, event) | ScenarioEvent::Step(step, event) => {
+                    self.handle_step_event(state, feature, scenario, step, event)
+                }
 
-                CucumberEvent::Feature(
-                    _feature,
-                    FeatureEvent::Scenario(
-                        _scenario,
-                        ScenarioEvent::Step(_step, StepEvent::Starting),
-                    ),
-                ) => state.add_step(
-                    _feature.name.to_owned(),
-                    _scenario.name.to_owned(),
-                    _step.value.to_owned(),
-                    _step.keyword.to_owned(),
-                ),
+                ScenarioEvent::Passed => {
+                    state.set_scenario_result(feature, scenario, StatResult::Passed)
+                }
 
-                CucumberEvent::Feature(_feature, FeatureEvent::Starting) => {
-                    state.add_feature(_feature.name.to_owned())
+                ScenarioEvent::Skipped => {
+                    state.set_scenario_result(feature, scenario, StatResult::Skipped)
                 }
 
-                CucumberEvent::Feature(
-                    ref _feature,
-                    FeatureEvent::Scenario(ref _scenario, ScenarioEvent::Starting(_)),
-                ) => state.add_scenario(_feature.name.to_owned(), _scenario.name.to_owned()),
+                ScenarioEvent::Failed(FailureKind::Panic) => {
+                    state.set_scenario_result(feature, scenario, StatResult::Failed)
+                }
 
-                _ => {}
+                ScenarioEvent::Failed(FailureKind::TimedOut) => {
+                    state.set_scenario_result(feature, scenario, StatResult::TimedOut)
+                }
             }
         }
-    }
-
-    pub fn print_test_results(stats: &RunStats) {
-        println!("");
-        println!("Result overview!");
-        println!("------------------------------------------------");
-        println!("Total features: {}", stats.total_featuress);
-        println!("Total scenarions: {}", stats.total_scenarios);
-        println!("Skipped scenarios: {}", stats.skipped_scenarios);
-        println!("Passed scenarios: {}", stats.passed_scenarios);
-        println!("Failed scenarios: {}", stats.failed_scenarios);
-        println!("------------------------------------------------");
 
-        if stats.features.is_empty() {
-            return;
-        }
+        fn handle_step_event(
+            &self,
+            state: &mut EventHandlerState,
+            feature: &gherkin::Feature,
+            scenario: &gherkin::Scenario,
+            step: &gherkin::Step,
+            event: &StepEvent,
+        ) {
+            match event {
+                StepEvent::Starting => {
+                    let patterns = self.get_matching_patterns(step);
+                    let step = state.start_step(feature, scenario, step);
+                    if patterns.len() > 1 {
+                        step.set_ambiguous(patterns);
+                    }
+                }
 
-        println!("");
-        println!("Feature overview:");
-        println!("------------------------------------------------");
+                StepEvent::Passed(output) => state
+                    .set_step_result(feature, scenario, step, StatResult::Passed)
+                    .set_output(output),
 
-        stats.features.iter().for_each(|f| {
-            println!("{}", f);
-            println!("");
-            f.scenarios.iter().for_each(|sc| {
-                println!("\t{}", sc);
-                sc.steps.iter().for_each(|st| {
-                    println!("\t\t{}", st);
-                });
-                println!("");
-            });
-        });
+                StepEvent::Failed(StepFailureKind::Panic(output, info)) => state
+                    .set_step_result(feature, scenario, step, StatResult::Failed)
+                    .set_panic(output, info),
 
-        println!("");
-    }
+                StepEvent::Failed(StepFailureKind::TimedOut) => state
+                    .set_step_result(feature, scenario, step, StatResult::TimedOut)
+                    .set_timeout(),
 
-    pub fn write_result_file(filename: &String, stats: &RunStats) {
-        let _ = std::fs::create_dir("./out");
-        let output = serde_json::to_string(&stats).unwrap();
-        let _ = std::fs::write(String::from("./out/") + filename, output);
-    }
-}
+                StepEvent::Unimplemented => state
+                    .set_step_result(feature, scenario, step, StatResult::Undefined)
+                    .set_undefined(),
+
+                StepEvent::Skipped => {
+                    state.set_step_result(feature, scenario, step, StatResult::Pending);
+                }
+            }
+
+            if let Some(recorder) = &self.recorder {
+                if *event != StepEvent::Starting {
+                    state.add_attachments(feature, scenario, step, recorder.drain());
+                }
+            }
+        }
+    }
+
+    impl EventHandler for RunEventHandler {
+        fn handle_event(&mut self, event: &CucumberEvent) {
+            let mut state = self.state.lock().unwrap();
+
+            match event {
+                CucumberEvent::Starting => state.timing = Timing::started(),
+
+                CucumberEvent::Finished(_) => state.timing.finish(),
+
+                CucumberEvent::Feature(_feature, FeatureEvent::Starting) => {
+                    state.add_feature(_feature)
+                }
+
+                CucumberEvent::Feature(_feature, FeatureEvent::Finished) => {
+                    state.finish_feature(_feature)
+                }
+
+                CucumberEvent::Feature(_feature, FeatureEvent::Scenario(_scenario, _event)) => {
+                    self.handle_scenario_event(&mut state, _feature, None, _scenario, _event)
+                }
+
+                CucumberEvent::Feature(
+                    _feature,
+                    FeatureEvent::Rule(_rule, RuleEvent::Scenario(_scenario, _event)),
+                ) => {
+                    self.handle_scenario_event(&mut state, _feature, Some(_rule), _scenario, _event)
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    /// Forwards every cucumber event to all of its handlers, in the order they were added, so
+    /// that the default output, the stats collection and the event stream can run together.
+    #[derive(Default)]
+    pub struct CompositeEventHandler {
+        handlers: Vec<Box<dyn EventHandler>>,
+    }
+
+    impl CompositeEventHandler {
+        pub fn new(handlers: Vec<Box<dyn EventHandler>>) -> Self {
+            Self { handlers }
+        }
+
+        pub fn add<H: EventHandler>(&mut self, handler: H) {
+            self.handlers.push(Box::new(handler));
+        }
+    }
+
+    impl EventHandler for CompositeEventHandler {
+        fn handle_event(&mut self, event: &CucumberEvent) {
+            for handler in self.handlers.iter_mut() {
+                handler.handle_event(event);
+            }
+        }
+    }
+
+    /// Appends one JSON line per cucumber event to a file, or to stdout when the destination
+    /// is `-`, flushing after every line so that tailing readers and crashed runs keep what
+    /// happened so far.
+    pub struct EventStreamHandler {
+        writer: LineWriter,
+    }
+
+    #[derive(Default, Serialize)]
+    pub struct StreamEvent {
+        pub timestamp_micros: u64,
+        pub event: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub feature: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub path: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub rule: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub scenario: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub example_values: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub step: Option<String>,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        pub background: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub line: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub result: Option<StatResult>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub error_message: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub failure_location: Option<String>,
+    }
+
+    impl EventStreamHandler {
+        pub fn new(destination: &str) -> Result<Self> {
+            Ok(Self::from_writer(open_stream(destination, true)?))
+        }
+
+        pub fn from_writer(writer: Box<dyn std::io::Write>) -> Self {
+            Self {
+                writer: LineWriter::new("event stream", writer),
+            }
+        }
+
+        fn write_event(&mut self, event: StreamEvent) {
+            if let Ok(line) = serde_json::to_string(&event) {
+                self.writer.write_line(&line);
+            }
+        }
+    }
+
+    impl StreamEvent {
+        fn new(event: &'static str) -> Self {
+            Self {
+                timestamp_micros: get_now_micros(),
+                event,
+                ..Self::default()
+            }
+        }
+
+        fn with_feature(event: &'static str, feature: &gherkin::Feature) -> Self {
+            Self {
+                feature: Some(feature.name.to_owned()),
+                path: get_feature_path(feature),
+                line: Some(feature.position.line),
+                ..Self::new(event)
+            }
+        }
+
+        fn with_scenario(
+            event: &'static str,
+            feature: &gherkin::Feature,
+            rule: Option<&gherkin::Rule>,
+            scenario: &gherkin::Scenario,
+        ) -> Self {
+            Self {
+                rule: rule.map(|r| r.name.to_owned()),
+                scenario: Some(scenario.name.to_owned()),
+                line: Some(scenario.position.line),
+                ..Self::with_feature(event, feature)
+            }
+        }
+
+        fn set_step_failure(&mut self, failure: &StepFailureKind) {
+            match failure {
+                StepFailureKind::Panic(_, info) => {
+                    self.result = Some(StatResult::Failed);
+                    self.error_message = Some(strip_ansi(&info.payload).trim().to_string());
+                    self.failure_location = Some(format!(
+                        "{}:{}:{}",
+                        info.location.file, info.location.line, info.location.column
+                    ));
+                }
+                StepFailureKind::TimedOut => {
+                    self.result = Some(StatResult::TimedOut);
+                    self.error_message = Some(String::from("Step timed out"));
+                }
+            }
+        }
+    }
+
+    fn get_stream_result(kind: &FailureKind) -> StatResult {
+        match kind {
+            FailureKind::Panic => StatResult::Failed,
+            FailureKind::TimedOut => StatResult::TimedOut,
+        }
+    }
+
+    impl EventStreamHandler {
+        fn handle_scenario_event(
+            &mut self,
+            feature: &gherkin::Feature,
+            rule: Option<&gherkin::Rule>,
+            scenario: &gherkin::Scenario,
+            event: &ScenarioEvent,
+        ) {
+            let new_event = |name| StreamEvent::with_scenario(name, feature, rule, scenario);
+            let finished = |result| StreamEvent {
+                result: Some(result),
+                ..new_event("scenario_finished")
+            };
+
+            let stream_event = match event {
+                ScenarioEvent::Starting(examples) => StreamEvent {
+                    example_values: match examples.is_empty() {
+                        true => None,
+                        false => Some(examples.to_string()),
+                    },
+                    ..new_event("scenario_started")
+                },
+                ScenarioEvent::Background(step, event) => {
+                    Self::get_step_event(new_event, step, true, event)
+                }
+                ScenarioEvent::Step(step, event) => {
+                    Self::get_step_event(new_event, step, false, event)
+                }
+                ScenarioEvent::Passed => finished(StatResult::Passed),
+                ScenarioEvent::Skipped => finished(StatResult::Skipped),
+                ScenarioEvent::Failed(kind) => finished(get_stream_result(kind)),
+            };
+
+            self.write_event(stream_event);
+        }
+
+        fn get_step_event(
+            new_event: impl Fn(&'static str) -> StreamEvent,
+            step: &gherkin::Step,
+            background: bool,
+            event: &StepEvent,
+        ) -> StreamEvent {
+            let name = match event {
+                StepEvent::Starting => "step_started",
+                _ => "step_finished",
+            };
+
+            let mut stream_event = StreamEvent {
+                step: Some(format!("{} {}", step.keyword, step.value)),
+                background,
+                line: Some(step.position.line),
+                ..new_event(name)
+            };
+
+            match event {
+                StepEvent::Starting => {}
+                StepEvent::Passed(_) => stream_event.result = Some(StatResult::Passed),
+                StepEvent::Skipped => stream_event.result = Some(StatResult::Pending),
+                StepEvent::Unimplemented => {
+                    stream_event.result = Some(StatResult::Undefined);
+                    stream_event.error_message =
+                        Some(String::from("No step definition matches this step"));
+                }
+                StepEvent::Failed(failure) => stream_event.set_step_failure(failure),
+            }
+
+            stream_event
+        }
+    }
+
+    impl EventStreamHandler {
+        fn handle_rule_event(
+            &mut self,
+            feature: &gherkin::Feature,
+            rule: &gherkin::Rule,
+            event: &RuleEvent,
+        ) {
+            let (name, result) = match event {
+                RuleEvent::Scenario(scenario, event) => {
+                    return self.handle_scenario_event(feature, Some(rule), scenario, event)
+                }
+                RuleEvent::Starting => ("rule_started", None),
+                RuleEvent::Passed => ("rule_finished", Some(StatResult::Passed)),
+                RuleEvent::Skipped => ("rule_finished", Some(StatResult::Skipped)),
+                RuleEvent::Failed(kind) => ("rule_finished", Some(get_stream_result(kind))),
+            };
+
+            self.write_event(StreamEvent {
+                rule: Some(rule.name.to_owned()),
+                line: Some(rule.position.line),
+                result,
+                ..StreamEvent::with_feature(name, feature)
+            })
+        }
+    }
+
+    impl EventHandler for EventStreamHandler {
+        fn handle_event(&mut self, event: &CucumberEvent) {
+            match event {
+                CucumberEvent::Starting => self.write_event(StreamEvent::new("run_started")),
+
+                CucumberEvent::Finished(_) => self.write_event(StreamEvent::new("run_finished")),
+
+                CucumberEvent::Feature(feature, FeatureEvent::Starting) => {
+                    self.write_event(StreamEvent::with_feature("feature_started", feature))
+                }
+
+                CucumberEvent::Feature(feature, FeatureEvent::Finished) => {
+                    self.write_event(StreamEvent::with_feature("feature_finished", feature))
+                }
+
+                CucumberEvent::Feature(feature, FeatureEvent::Scenario(scenario, event)) => {
+                    self.handle_scenario_event(feature, None, scenario, event)
+                }
+
+                CucumberEvent::Feature(feature, FeatureEvent::Rule(rule, event)) => {
+                    self.handle_rule_event(feature, rule, event)
+                }
+            }
+        }
+    }
+
+    /// Emits the run as Cucumber Messages NDJSON, the protocol read by the official cucumber
+    /// formatters. The source, gherkin document, pickles and test cases of a feature are sent
+    /// when it starts. cucumber_rust doesn't expose its step definitions, so no
+    /// `stepDefinition` messages are sent and test steps don't reference any.
+    pub struct CucumberMessagesHandler {
+        writer: LineWriter,
+        next_id: usize,
+        /// Text of the feature being sent, to locate its tags.
+        source: String,
+        test_cases: Vec<MessageTestCase>,
+        current: Option<MessageTestCaseRun>,
+        /// Whether a step ended in a status that fails the run, by the rule of
+        /// `RunStats::failed`.
+        failed: bool,
+    }
+
+    struct MessageTestCase {
+        id: String,
+        uri: Option<String>,
+        feature_line: usize,
+        scenario_line: usize,
+        steps: Vec<MessageTestStep>,
+        started: bool,
+    }
+
+    struct MessageTestStep {
+        id: String,
+        line: usize,
+        background: bool,
+    }
+
+    struct MessageTestCaseRun {
+        id: String,
+        test_case: usize,
+        started_steps: Vec<(String, u64)>,
+        finished_steps: Vec<String>,
+    }
+
+    const MESSAGES_PROTOCOL_VERSION: &str = "19.1.2";
+
+    impl CucumberMessagesHandler {
+        pub fn new(destination: &str) -> Result<Self> {
+            Ok(Self::from_writer(open_stream(destination, false)?))
+        }
+
+        pub fn from_writer(writer: Box<dyn std::io::Write>) -> Self {
+            Self {
+                writer: LineWriter::new("messages stream", writer),
+                next_id: 0,
+                source: String::default(),
+                test_cases: Vec::default(),
+                current: None,
+                failed: false,
+            }
+        }
+
+        fn write_message(&mut self, message: serde_json::Value) {
+            self.writer.write_line(&message.to_string());
+        }
+
+        fn get_next_id(&mut self) -> String {
+            self.next_id += 1;
+            (self.next_id - 1).to_string()
+        }
+
+        fn start_run(&mut self) {
+            self.write_message(serde_json::json!({
+                "meta": {
+                    "protocolVersion": MESSAGES_PROTOCOL_VERSION,
+                    "implementation": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION")
+                    },
+                    "runtime": { "name": "rust" },
+                    "os": { "name": std::env::consts::OS },
+                    "cpu": { "name": std::env::consts::ARCH }
+                }
+            }));
+            self.write_message(serde_json::json!({
+                "testRunStarted": { "timestamp": get_message_timestamp(get_now_micros()) }
+            }));
+        }
+
+        fn finish_run(&mut self) {
+            self.write_message(serde_json::json!({
+                "testRunFinished": {
+                    "success": !self.failed,
+                    "timestamp": get_message_timestamp(get_now_micros())
+                }
+            }));
+        }
+
+        /// Sends the source, the gherkin document and then the pickle and test case of every
+        /// scenario, or of every example row of a scenario outline.
+        fn start_feature(&mut self, feature: &gherkin::Feature) {
+            let uri = get_feature_path(feature);
+            let data = uri
+                .as_ref()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .unwrap_or_default();
+            self.write_message(serde_json::json!({
+                "source": {
+                    "uri": uri,
+                    "data": data,
+                    "mediaType": "text/x.cucumber.gherkin+plain"
+                }
+            }));
+            self.source = data;
+
+            let background = feature
+                .background
+                .as_ref()
+                .map(|b| self.get_message_background(b));
+            let mut children: Vec<serde_json::Value> = background
+                .as_ref()
+                .map(|(b, _)| serde_json::json!({ "background": b }))
+                .into_iter()
+                .collect();
+            let background_ids = background.map(|(_, ids)| ids).unwrap_or_default();
+
+            let feature_tags = self.get_message_tags(&feature.tags, feature.position.line);
+            let mut pickles = Vec::default();
+            for scenario in feature.scenarios.iter() {
+                let (child, mut ids) = self.get_message_scenario(scenario);
+                children.push(serde_json::json!({ "scenario": child }));
+                ids.tags.splice(0..0, feature_tags.iter().cloned());
+                pickles.push((scenario, ids));
+            }
+            for rule in feature.rules.iter() {
+                let rule_id = self.get_next_id();
+                let rule_tags = self.get_message_tags(&rule.tags, rule.position.line);
+                let mut rule_children = Vec::default();
+                for scenario in rule.scenarios.iter() {
+                    let (child, mut ids) = self.get_message_scenario(scenario);
+                    rule_children.push(serde_json::json!({ "scenario": child }));
+                    ids.tags
+                        .splice(0..0, feature_tags.iter().chain(rule_tags.iter()).cloned());
+                    pickles.push((scenario, ids));
+                }
+                children.push(serde_json::json!({
+                    "rule": {
+                        "id": rule_id,
+                        "location": get_message_location(rule.position.line),
+                        "tags": rule_tags,
+                        "keyword": rule.keyword,
+                        "name": rule.name,
+                        "description": "",
+                        "children": rule_children
+                    }
+                }));
+            }
+
+            self.write_message(serde_json::json!({
+                "gherkinDocument": {
+                    "uri": uri,
+                    "feature": {
+                        "location": get_message_location(feature.position.line),
+                        "tags": feature_tags,
+                        "language": "en",
+                        "keyword": feature.keyword,
+                        "name": feature.name,
+                        "description": feature.description.clone().unwrap_or_default(),
+                        "children": children
+                    },
+                    "comments": []
+                }
+            }));
+
+            for (scenario, ids) in pickles {
+                self.add_pickles(feature, scenario, &background_ids, &ids);
+            }
+        }
+
+        /// Returns the background message and the ids given to its steps.
+        fn get_message_background(
+            &mut self,
+            background: &gherkin::Background,
+        ) -> (serde_json::Value, Vec<String>) {
+            let id = self.get_next_id();
+            let (steps, ids) = self.get_message_steps(&background.steps);
+            let message = serde_json::json!({
+                "id": id,
+                "location": get_message_location(background.position.line),
+                "keyword": background.keyword,
+                "name": "",
+                "description": "",
+                "steps": steps
+            });
+
+            (message, ids)
+        }
+
+        /// Returns the scenario message and the ids given to the scenario, its steps and its
+        /// example rows, along with the tags of the scenario and its examples.
+        fn get_message_scenario(
+            &mut self,
+            scenario: &gherkin::Scenario,
+        ) -> (serde_json::Value, MessageScenarioIds) {
+            let id = self.get_next_id();
+            let tags = self.get_message_tags(&scenario.tags, scenario.position.line);
+            let (steps, step_ids) = self.get_message_steps(&scenario.steps);
+            let mut row_ids = Vec::default();
+            let mut examples_tags = Vec::default();
+            let examples: Vec<serde_json::Value> = scenario
+                .examples
+                .iter()
+                .map(|examples| {
+                    let examples_id = self.get_next_id();
+                    let line = examples.table.position.line;
+                    let mut rows: Vec<serde_json::Value> = examples
+                        .table
+                        .rows
+                        .iter()
+                        .enumerate()
+                        .map(|(index, row)| {
+                            let row_id = self.get_next_id();
+                            if index > 0 {
+                                row_ids.push(row_id.to_owned());
+                            }
+                            serde_json::json!({
+                                "id": row_id,
+                                "location": get_message_location(line + index),
+                                "cells": row
+                                    .iter()
+                                    .map(|value| serde_json::json!({
+                                        "location": get_message_location(line + index),
+                                        "value": value
+                                    }))
+                                    .collect::<Vec<serde_json::Value>>()
+                            })
+                        })
+                        .collect();
+                    let header = match rows.is_empty() {
+                        true => serde_json::Value::Null,
+                        false => rows.remove(0),
+                    };
+                    let tags = self.get_message_tags(&examples.tags, examples.position.line);
+                    examples_tags.extend(tags.iter().cloned());
+                    serde_json::json!({
+                        "id": examples_id,
+                        "location": get_message_location(examples.position.line),
+                        "tags": tags,
+                        "keyword": examples.keyword,
+                        "name": "",
+                        "description": "",
+                        "tableHeader": header,
+                        "tableBody": rows
+                    })
+                })
+                .collect();
+
+            let message = serde_json::json!({
+                "id": id,
+                "location": get_message_location(scenario.position.line),
+                "tags": tags.clone(),
+                "keyword": scenario.keyword,
+                "name": scenario.name,
+                "description": "",
+                "steps": steps,
+                "examples": examples
+            });
+
+            (
+                message,
+                MessageScenarioIds {
+                    id,
+                    step_ids,
+                    row_ids,
+                    tags: tags.into_iter().chain(examples_tags).collect(),
+                },
+            )
+        }
+
+        fn get_message_steps(
+            &mut self,
+            steps: &[gherkin::Step],
+        ) -> (Vec<serde_json::Value>, Vec<String>) {
+            steps
+                .iter()
+                .map(|step| {
+                    let id = self.get_next_id();
+                    let message = serde_json::json!({
+                        "id": id,
+                        "location": get_message_location(step.position.line),
+                        "keyword": format!("{} ", step.keyword),
+                        "text": step.value
+                    });
+                    (message, id)
+                })
+                .unzip()
+        }
+
+        /// gherkin_rust doesn't keep where tags are, so they are looked up in the feature source
+        /// above the element they belong to, and left without a location when it isn't found.
+        fn get_message_tags(&mut self, tags: &[String], line: usize) -> Vec<serde_json::Value> {
+            let mut locations = get_tag_locations(&self.source, line);
+            tags.iter()
+                .map(|tag| {
+                    let name = format!("@{}", tag);
+                    let mut message = serde_json::json!({ "name": name, "id": self.get_next_id() });
+                    if let Some(index) = locations.iter().position(|(_, _, n)| *n == name) {
+                        let (line, column, _) = locations.remove(index);
+                        message["location"] = serde_json::json!({ "line": line, "column": column });
+                    }
+                    message
+                })
+                .collect()
+        }
+
+        fn add_pickles(
+            &mut self,
+            feature: &gherkin::Feature,
+            scenario: &gherkin::Scenario,
+            background_ids: &[String],
+            ids: &MessageScenarioIds,
+        ) {
+            let uri = get_feature_path(feature);
+            let rows: Vec<(ExampleValues, Option<&String>)> =
+                match ExampleValues::from_examples(&scenario.examples) {
+                    values if ids.row_ids.is_empty() => {
+                        values.into_iter().map(|v| (v, None)).collect()
+                    }
+                    values => values
+                        .into_iter()
+                        .zip(ids.row_ids.iter().map(Some))
+                        .collect(),
+                };
+            let tags: Vec<serde_json::Value> = ids
+                .tags
+                .iter()
+                .map(|t| serde_json::json!({ "name": t["name"], "astNodeId": t["id"] }))
+                .collect();
+
+            for (examples, row_id) in rows {
+                let pickle_id = self.get_next_id();
+                let background_steps = feature
+                    .background
+                    .iter()
+                    .flat_map(|b| b.steps.iter())
+                    .zip(background_ids.iter())
+                    .map(|(step, id)| (step, step.value.to_owned(), vec![id.to_owned()], true));
+                let scenario_steps =
+                    scenario
+                        .steps
+                        .iter()
+                        .zip(ids.step_ids.iter())
+                        .map(|(step, id)| {
+                            let ast_node_ids = std::iter::once(id).chain(row_id).cloned().collect();
+                            (
+                                step,
+                                examples.insert_values(&step.value),
+                                ast_node_ids,
+                                false,
+                            )
+                        });
+
+                let mut pickle_steps = Vec::default();
+                let mut test_steps = Vec::default();
+                for (step, text, ast_node_ids, background) in
+                    background_steps.chain(scenario_steps).collect::<Vec<_>>()
+                {
+                    let pickle_step_id = self.get_next_id();
+                    let test_step_id = self.get_next_id();
+                    pickle_steps.push(serde_json::json!({
+                        "id": pickle_step_id,
+                        "text": text,
+                        "type": get_message_step_type(step.ty),
+                        "astNodeIds": ast_node_ids
+                    }));
+                    test_steps.push((
+                        serde_json::json!({
+                            "id": test_step_id,
+                            "pickleStepId": pickle_step_id,
+                            "stepDefinitionIds": [],
+                            "stepMatchArgumentsLists": []
+                        }),
+                        MessageTestStep {
+                            id: test_step_id,
+                            line: step.position.line,
+                            background,
+                        },
+                    ));
+                }
+
+                self.write_message(serde_json::json!({
+                    "pickle": {
+                        "id": pickle_id,
+                        "uri": uri,
+                        "name": examples.insert_values(&scenario.name),
+                        "language": "en",
+                        "steps": pickle_steps,
+                        "tags": tags,
+                        "astNodeIds": std::iter::once(&ids.id).chain(row_id).collect::<Vec<&String>>()
+                    }
+                }));
+
+                let test_case_id = self.get_next_id();
+                let (messages, steps): (Vec<serde_json::Value>, Vec<MessageTestStep>) =
+                    test_steps.into_iter().unzip();
+                self.write_message(serde_json::json!({
+                    "testCase": {
+                        "id": test_case_id,
+                        "pickleId": pickle_id,
+                        "testSteps": messages
+                    }
+                }));
+                self.test_cases.push(MessageTestCase {
+                    id: test_case_id,
+                    uri: uri.clone(),
+                    feature_line: feature.position.line,
+                    scenario_line: scenario.position.line,
+                    steps,
+                    started: false,
+                });
+            }
+        }
+    }
+
+    struct MessageScenarioIds {
+        id: String,
+        step_ids: Vec<String>,
+        row_ids: Vec<String>,
+        /// Tag messages of the feature, rule, scenario and examples the pickles inherit.
+        tags: Vec<serde_json::Value>,
+    }
+
+    impl CucumberMessagesHandler {
+        /// Example rows start in order, so the row that starts is the first not started yet.
+        fn start_test_case(&mut self, feature: &gherkin::Feature, scenario: &gherkin::Scenario) {
+            let uri = get_feature_path(feature);
+            let index = self.test_cases.iter().position(|tc| {
+                !tc.started
+                    && tc.uri == uri
+                    && tc.feature_line == feature.position.line
+                    && tc.scenario_line == scenario.position.line
+            });
+            let index = match index {
+                Some(index) => index,
+                None => return,
+            };
+
+            self.test_cases[index].started = true;
+            let id = self.get_next_id();
+            self.write_message(serde_json::json!({
+                "testCaseStarted": {
+                    "id": id,
+                    "testCaseId": self.test_cases[index].id,
+                    "attempt": 0,
+                    "timestamp": get_message_timestamp(get_now_micros())
+                }
+            }));
+            self.current = Some(MessageTestCaseRun {
+                id,
+                test_case: index,
+                started_steps: Vec::default(),
+                finished_steps: Vec::default(),
+            });
+        }
+
+        fn get_test_step_id(&self, step: &gherkin::Step, background: bool) -> Option<String> {
+            let run = self.current.as_ref()?;
+            self.test_cases[run.test_case]
+                .steps
+                .iter()
+                .find(|st| {
+                    st.line == step.position.line
+                        && st.background == background
+                        && !run.finished_steps.contains(&st.id)
+                })
+                .map(|st| st.id.to_owned())
+        }
+
+        fn start_test_step(&mut self, test_step_id: String) {
+            let now = get_now_micros();
+            let run = match self.current.as_mut() {
+                Some(run) => run,
+                None => return,
+            };
+
+            run.started_steps.push((test_step_id.to_owned(), now));
+            let message = serde_json::json!({
+                "testStepStarted": {
+                    "testCaseStartedId": run.id,
+                    "testStepId": test_step_id,
+                    "timestamp": get_message_timestamp(now)
+                }
+            });
+            self.write_message(message);
+        }
+
+        fn finish_test_step(
+            &mut self,
+            test_step_id: String,
+            status: &str,
+            message: Option<String>,
+        ) {
+            let now = get_now_micros();
+            let run = match self.current.as_mut() {
+                Some(run) => run,
+                None => return,
+            };
+
+            let started = run
+                .started_steps
+                .iter()
+                .find(|(id, _)| *id == test_step_id)
+                .map(|(_, started)| *started)
+                .unwrap_or(now);
+            run.finished_steps.push(test_step_id.to_owned());
+            let mut result = serde_json::json!({
+                "status": status,
+                "duration": get_message_timestamp(now.saturating_sub(started))
+            });
+            if let Some(message) = message {
+                result["message"] = serde_json::Value::String(message);
+            }
+
+            let message = serde_json::json!({
+                "testStepFinished": {
+                    "testCaseStartedId": run.id,
+                    "testStepId": test_step_id,
+                    "testStepResult": result,
+                    "timestamp": get_message_timestamp(now)
+                }
+            });
+            self.write_message(message);
+        }
+
+        /// Steps after a failure never run, so they are reported as skipped before the test
+        /// case finishes.
+        fn finish_test_case(&mut self) {
+            let (run_id, remaining) = match self.current.as_ref() {
+                Some(run) => (
+                    run.id.to_owned(),
+                    self.test_cases[run.test_case]
+                        .steps
+                        .iter()
+                        .filter(|st| !run.finished_steps.contains(&st.id))
+                        .map(|st| st.id.to_owned())
+                        .collect::<Vec<String>>(),
+                ),
+                None => return,
+            };
+
+            for test_step_id in remaining {
+                self.start_test_step(test_step_id.to_owned());
+                self.finish_test_step(test_step_id, "SKIPPED", None);
+            }
+
+            self.current = None;
+            self.write_message(serde_json::json!({
+                "testCaseFinished": {
+                    "testCaseStartedId": run_id,
+                    "timestamp": get_message_timestamp(get_now_micros()),
+                    "willBeRetried": false
+                }
+            }));
+        }
+
+        fn handle_step_event(&mut self, step: &gherkin::Step, background: bool, event: &StepEvent) {
+            let test_step_id = match self.get_test_step_id(step, background) {
+                Some(id) => id,
+                None => return,
+            };
+
+            let (status, result, message) = match event {
+                StepEvent::Starting => return self.start_test_step(test_step_id),
+                StepEvent::Passed(_) => ("PASSED", StatResult::Passed, None),
+                StepEvent::Skipped => ("PENDING", StatResult::Pending, None),
+                StepEvent::Unimplemented => ("UNDEFINED", StatResult::Undefined, None),
+                StepEvent::Failed(StepFailureKind::TimedOut) => (
+                    "FAILED",
+                    StatResult::TimedOut,
+                    Some(String::from("Step timed out")),
+                ),
+                StepEvent::Failed(StepFailureKind::Panic(_, info)) => (
+                    "FAILED",
+                    StatResult::Failed,
+                    Some(format!(
+                        "{}\nat {}:{}:{}",
+                        strip_ansi(&info.payload).trim(),
+                        info.location.file,
+                        info.location.line,
+                        info.location.column
+                    )),
+                ),
+            };
+
+            self.failed |= result.is_failure();
+            self.finish_test_step(test_step_id, status, message);
+        }
+
+        fn handle_scenario_event(
+            &mut self,
+            feature: &gherkin::Feature,
+            scenario: &gherkin::Scenario,
+            event: &ScenarioEvent,
+        ) {
+            match event {
+                ScenarioEvent::Starting(_) => self.start_test_case(feature, scenario),
+                ScenarioEvent::Background(step, event) => self.handle_step_event(step, true, event),
+                ScenarioEvent::Step(step, event) => self.handle_step_event(step, false, event),
+                ScenarioEvent::Passed | ScenarioEvent::Skipped | ScenarioEvent::Failed(_) => {
+                    self.finish_test_case()
+                }
+            }
+        }
+    }
+
+    impl EventHandler for CucumberMessagesHandler {
+        fn handle_event(&mut self, event: &CucumberEvent) {
+            match event {
+                CucumberEvent::Starting => self.start_run(),
+
+                CucumberEvent::Finished(_) => self.finish_run(),
+
+                CucumberEvent::Feature(feature, FeatureEvent::Starting) => {
+                    self.start_feature(feature)
+                }
+
+                CucumberEvent::Feature(feature, FeatureEvent::Scenario(scenario, event))
+                | CucumberEvent::Feature(
+                    feature,
+                    FeatureEvent::Rule(_, RuleEvent::Scenario(scenario, event)),
+                ) => self.handle_scenario_event(feature, scenario, event),
+
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the line, column and name of the tags on the tag lines right above `line`, in
+    /// source order, skipping blank lines and comments.
+    fn get_tag_locations(source: &str, line: usize) -> Vec<(usize, usize, String)> {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut locations = Vec::default();
+        for index in (0..line.saturating_sub(1).min(lines.len())).rev() {
+            let text = lines[index].trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            if !text.starts_with('@') {
+                break;
+            }
+
+            let mut line_tags = Vec::default();
+            let mut column = 1;
+            for word in lines[index].split(char::is_whitespace) {
+                if word.starts_with('#') {
+                    break;
+                }
+                if !word.is_empty() {
+                    line_tags.push((index + 1, column, word.to_string()));
+                }
+                column += word.chars().count() + 1;
+            }
+            locations.splice(0..0, line_tags);
+        }
+
+        locations
+    }
+
+    fn get_message_location(line: usize) -> serde_json::Value {
+        serde_json::json!({ "line": line })
+    }
+
+    fn get_message_timestamp(micros: u64) -> serde_json::Value {
+        serde_json::json!({
+            "seconds": micros / 1_000_000,
+            "nanos": (micros % 1_000_000) * 1000
+        })
+    }
+
+    fn get_message_step_type(ty: gherkin::StepType) -> &'static str {
+        match ty {
+            gherkin::StepType::Given => "Context",
+            gherkin::StepType::When => "Action",
+            gherkin::StepType::Then => "Outcome",
+        }
+    }
+
+    /// Opens a stream destination, stdout for `-`, either appending to an existing file or
+    /// replacing it.
+    fn open_stream(destination: &str, append: bool) -> Result<Box<dyn std::io::Write>> {
+        let writer: Box<dyn std::io::Write> = match destination {
+            "-" => Box::new(std::io::stdout()),
+            _ => Box::new(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(append)
+                    .truncate(!append)
+                    .open(destination)?,
+            ),
+        };
+
+        Ok(writer)
+    }
+
+    /// Writes and flushes one line at a time. A failing write, e.g. on a full disk or a closed
+    /// pipe, can't fail the run from an event handler, so the first failure is reported on
+    /// stderr and nothing more is written.
+    struct LineWriter {
+        name: &'static str,
+        writer: Box<dyn std::io::Write>,
+        failed: bool,
+    }
+
+    impl LineWriter {
+        fn new(name: &'static str, writer: Box<dyn std::io::Write>) -> Self {
+            Self {
+                name,
+                writer,
+                failed: false,
+            }
+        }
+
+        fn write_line(&mut self, line: &str) {
+            if self.failed {
+                return;
+            }
+
+            let result = writeln!(self.writer, "{}", line).and_then(|_| self.writer.flush());
+            if let Err(e) = result {
+                eprintln!("Unable to write the {}: {}", self.name, e);
+                self.failed = true;
+            }
+        }
+    }
+
+    /// Whether the console output is colored: `Auto` colors it only when stdout is a terminal
+    /// and the `NO_COLOR` environment variable is not set.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum ColorMode {
+        Auto,
+        Always,
+        Never,
+    }
+
+    impl std::str::FromStr for ColorMode {
+        type Err = String;
+
+        fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+            match s {
+                "auto" => Ok(Self::Auto),
+                "always" => Ok(Self::Always),
+                "never" => Ok(Self::Never),
+                _ => Err(format!("Unknown color mode '{}'", s)),
+            }
+        }
+    }
+
+    impl ColorMode {
+        pub fn is_enabled(&self) -> bool {
+            match self {
+                Self::Always => true,
+                Self::Never => false,
+                Self::Auto => {
+                    atty::is(atty::Stream::Stdout) && std::env::var_os("NO_COLOR").is_none()
+                }
+            }
+        }
+    }
+
+    const CONSOLE_RESET: &str = "\x1b[0m";
+    const CONSOLE_BOLD: &str = "\x1b[1m";
+    const CONSOLE_DIM: &str = "\x1b[2m";
+
+    pub fn print_test_results(stats: &RunStats, color: ColorMode) {
+        print!("{}", get_console_report(stats, color.is_enabled()));
+    }
+
+    fn get_console_report(stats: &RunStats, colored: bool) -> String {
+        let paint = |value: &str, style: &str| match colored {
+            true => format!("{}{}{}", style, value, CONSOLE_RESET),
+            false => value.to_string(),
+        };
+
+        let mut output = String::from("\n");
+        stats.features.iter().for_each(|f| {
+            output += &format!(
+                "{} {}\n",
+                paint(&format!("{}: {}", f.keyword, f.name), CONSOLE_BOLD),
+                paint(
+                    &format!(
+                        "# {}{}",
+                        f.get_location(f.line),
+                        get_console_duration(&f.timing)
+                    ),
+                    CONSOLE_DIM
+                )
+            );
+
+            f.scenarios.iter().for_each(|sc| {
+                let result = sc.result.clone().unwrap_or(StatResult::Skipped);
+                output += &format!(
+                    "  {}{}\n",
+                    paint(
+                        &format!(
+                            "{} {}: {}",
+                            get_console_icon(&result),
+                            sc.keyword,
+                            sc.get_display_name()
+                        ),
+                        get_console_color(&result)
+                    ),
+                    paint(&get_console_duration(&sc.timing), CONSOLE_DIM)
+                );
+
+                sc.steps.iter().for_each(|st| {
+                    let result = st.result.clone().unwrap_or(StatResult::Skipped);
+                    output += &format!(
+                        "    {}{}\n",
+                        paint(
+                            &format!(
+                                "{} {} {}",
+                                get_console_icon(&result),
+                                st.keyword.trim(),
+                                st.name
+                            ),
+                            get_console_color(&result)
+                        ),
+                        paint(&get_console_duration(&st.timing), CONSOLE_DIM)
+                    );
+                    if let Some(error_message) = &st.error_message {
+                        error_message.lines().for_each(|line| {
+                            output += &format!(
+                                "        {}\n",
+                                paint(line.trim(), get_console_color(&result))
+                            )
+                        });
+                    }
+                    if let Some(location) = &st.failure_location {
+                        output += &format!(
+                            "        {}\n",
+                            paint(&format!("at {}", location), CONSOLE_DIM)
+                        );
+                    }
+                    st.attachments.iter().for_each(|a| {
+                        output += &format!("        {}\n", paint(&format!("⇄ {}", a), CONSOLE_DIM))
+                    });
+                });
+            });
+            output += "\n";
+        });
+
+        if !stats.tags.is_empty() {
+            output += &format!("{}\n", paint("Tags", CONSOLE_BOLD));
+            stats.tags.iter().for_each(|t| {
+                output += &format!(
+                    "  {}: {} scenarios, {} passed, {} failed, {} skipped {}\n",
+                    t.name,
+                    t.total_scenarios,
+                    t.passed_scenarios,
+                    t.failed_scenarios,
+                    t.skipped_scenarios,
+                    paint(
+                        &format!("({:.3} ms)", t.duration_micros as f64 / 1000.0),
+                        CONSOLE_DIM
+                    )
+                )
+            });
+            output += "\n";
+        }
+
+        let scenario_counts = vec![
+            (StatResult::Passed, stats.passed_scenarios),
+            (StatResult::Failed, stats.failed_scenarios),
+            (StatResult::Skipped, stats.skipped_scenarios),
+            (StatResult::Undefined, stats.undefined_scenarios),
+            (StatResult::Pending, stats.pending_scenarios),
+            (StatResult::Ambiguous, stats.ambiguous_scenarios),
+            (StatResult::TimedOut, stats.timed_out_scenarios),
+        ];
+        let steps: Vec<StatResult> = stats
+            .features
+            .iter()
+            .flat_map(|f| f.scenarios.iter())
+            .flat_map(|sc| sc.steps.iter())
+            .map(|st| st.result.clone().unwrap_or(StatResult::Skipped))
+            .collect();
+        let step_counts: Vec<(StatResult, u32)> = scenario_counts
+            .iter()
+            .map(|(result, _)| {
+                let count = steps.iter().filter(|st| *st == result).count();
+                (result.clone(), count as u32)
+            })
+            .collect();
+
+        let get_counts = |counts: &[(StatResult, u32)]| {
+            counts
+                .iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(result, count)| {
+                    paint(
+                        &format!("{} {}", count, result.to_string().to_lowercase()),
+                        get_console_color(result),
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        output += &format!(
+            "{} features\n{} scenarios ({})\n{} steps ({})\n",
+            stats.total_featuress,
+            stats.total_scenarios,
+            get_counts(&scenario_counts),
+            steps.len(),
+            get_counts(&step_counts)
+        );
+
+        let start = stats
+            .features
+            .iter()
+            .filter_map(|f| f.timing.start_micros)
+            .min();
+        let end = stats
+            .features
+            .iter()
+            .filter_map(|f| f.timing.end_micros)
+            .max();
+        if let (Some(start), Some(end)) = (start, end) {
+            output += &format!(
+                "Finished in {:.3} s\n",
+                end.saturating_sub(start) as f64 / 1_000_000.0
+            );
+        }
+
+        output
+    }
+
+    fn get_console_icon(result: &StatResult) -> &'static str {
+        match result {
+            StatResult::Passed => "✔",
+            StatResult::Failed => "✘",
+            StatResult::Skipped => "↷",
+            StatResult::Undefined => "?",
+            StatResult::Pending => "…",
+            StatResult::Ambiguous => "≠",
+            StatResult::TimedOut => "⏱",
+        }
+    }
+
+    fn get_console_color(result: &StatResult) -> &'static str {
+        match result {
+            StatResult::Passed => "\x1b[32m",
+            StatResult::Failed | StatResult::TimedOut => "\x1b[31m",
+            StatResult::Skipped => "\x1b[36m",
+            StatResult::Undefined | StatResult::Pending => "\x1b[33m",
+            StatResult::Ambiguous => "\x1b[35m",
+        }
+    }
+
+    fn get_console_duration(timing: &Timing) -> String {
+        match timing.get_duration_millis() {
+            Some(duration) => format!(" ({:.3} ms)", duration),
+            None => String::new(),
+        }
+    }
+
+    /// Writes the `RunStats` as JSON to `path`, or to `result.json` inside it when `path` is a
+    /// directory.
+    pub fn write_result_file(path: &str, stats: &RunStats, pretty: bool) -> Result<()> {
+        write_report(path, "result.json", &get_json(stats, pretty)?)
+    }
+
+    fn get_json<T: Serialize>(value: &T, pretty: bool) -> Result<String> {
+        let output = match pretty {
+            true => serde_json::to_string_pretty(value)?,
+            false => serde_json::to_string(value)?,
+        };
+
+        Ok(output)
+    }
+
+    pub fn merge_result_files(paths: &[String]) -> Result<RunStats> {
+        let reports = paths
+            .iter()
+            .map(|path| RunStats::load(path))
+            .collect::<Result<Vec<RunStats>>>()?;
+        Ok(RunStats::merge(&reports))
+    }
+
+    /// Scenarios whose duration grows less than this are not reported as regressions, whatever
+    /// the ratio, to leave out the noise of fast scenarios.
+    pub const DURATION_REGRESSION_MIN_MICROS: u64 = 100_000;
+
+    #[derive(Clone)]
+    pub struct ScenarioChange {
+        pub name: String,
+        pub previous: Option<StatResult>,
+        pub current: Option<StatResult>,
+    }
+
+    #[derive(Clone)]
+    pub struct DurationRegression {
+        pub name: String,
+        pub previous_micros: u64,
+        pub current_micros: u64,
+    }
+
+    #[derive(Default, Clone)]
+    pub struct RunDiff {
+        pub newly_failing: Vec<ScenarioChange>,
+        pub newly_passing: Vec<ScenarioChange>,
+        pub added: Vec<ScenarioChange>,
+        pub removed: Vec<ScenarioChange>,
+        pub duration_regressions: Vec<DurationRegression>,
+    }
+
+    impl RunDiff {
+        /// Whether a scenario fails now that did not fail, or did not exist, in the previous run.
+        pub fn has_new_failures(&self) -> bool {
+            !self.newly_failing.is_empty()
+                || self
+                    .added
+                    .iter()
+                    .any(|c| matches!(&c.current, Some(r) if r.is_failure()))
+        }
+    }
+
+    impl std::fmt::Display for ScenarioChange {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let get_status = |result: &Option<StatResult>| match result {
+                Some(result) => result.to_string(),
+                None => String::from("-"),
+            };
+
+            write!(
+                f,
+                "Scenario: '{}'; Previous: '{}'; Current: '{}'",
+                self.name,
+                get_status(&self.previous),
+                get_status(&self.current)
+            )
+        }
+    }
+
+    impl std::fmt::Display for DurationRegression {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "Scenario: '{}'; Previous: {:.3} ms; Current: {:.3} ms",
+                self.name,
+                self.previous_micros as f64 / 1000.0,
+                self.current_micros as f64 / 1000.0
+            )
+        }
+    }
+
+    impl std::fmt::Display for RunDiff {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let sections: [(&str, Vec<String>); 5] = [
+                (
+                    "Newly failing scenarios",
+                    self.newly_failing.iter().map(|c| c.to_string()).collect(),
+                ),
+                (
+                    "Newly passing scenarios",
+                    self.newly_passing.iter().map(|c| c.to_string()).collect(),
+                ),
+                (
+                    "Added scenarios",
+                    self.added.iter().map(|c| c.to_string()).collect(),
+                ),
+                (
+                    "Removed scenarios",
+                    self.removed.iter().map(|c| c.to_string()).collect(),
+                ),
+                (
+                    "Duration regressions",
+                    self.duration_regressions
+                        .iter()
+                        .map(|r| r.to_string())
+                        .collect(),
+                ),
+            ];
+
+            for (title, lines) in sections.iter() {
+                writeln!(f, "{}: {}", title, lines.len())?;
+                for line in lines {
+                    writeln!(f, "\t{}", line)?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    fn get_scenarios(stats: &RunStats) -> Vec<(String, &FeatureStats, &ScenarioStats)> {
+        stats
+            .features
+            .iter()
+            .flat_map(|f| {
+                f.scenarios
+                    .iter()
+                    .map(move |s| (format!("{} / {}", f.name, s.get_display_name()), f, s))
+            })
+            .collect()
+    }
+
+    fn is_same_scenario(
+        (_, feature, scenario): &(String, &FeatureStats, &ScenarioStats),
+        (_, other_feature, other_scenario): &(String, &FeatureStats, &ScenarioStats),
+    ) -> bool {
+        feature.is_same_feature(other_feature) && scenario.is_same_scenario(other_scenario)
+    }
+
+    /// Compares two runs scenario by scenario, matching them by feature file, line and example
+    /// row like `RunStats::merge`. A scenario is a duration regression when it is slower than
+    /// `regression_ratio` times its previous duration, e.g. `0.5` flags scenarios that are 50%
+    /// slower.
+    pub fn diff_results(previous: &RunStats, current: &RunStats, regression_ratio: f64) -> RunDiff {
+        let previous_scenarios = get_scenarios(previous);
+        let current_scenarios = get_scenarios(current);
+        let mut diff = RunDiff::default();
+
+        current_scenarios.iter().for_each(|entry| {
+            let (name, _, current) = entry;
+            let change = |previous: Option<&ScenarioStats>| ScenarioChange {
+                name: name.to_owned(),
+                previous: previous.and_then(|p| p.result.clone()),
+                current: current.result.clone(),
+            };
+
+            let previous = match previous_scenarios
+                .iter()
+                .find(|p| is_same_scenario(p, entry))
+            {
+                Some((_, _, previous)) => previous,
+                None => {
+                    diff.added.push(change(None));
+                    return;
+                }
+            };
+
+            let previous_result = previous.result.clone().unwrap_or(StatResult::Skipped);
+            let current_result = current.result.clone().unwrap_or(StatResult::Skipped);
+            if current_result.is_failure() && !previous_result.is_failure() {
+                diff.newly_failing.push(change(Some(previous)));
+            } else if current_result == StatResult::Passed && previous_result != StatResult::Passed
+            {
+                diff.newly_passing.push(change(Some(previous)));
+            }
+
+            if let (Some(previous_micros), Some(current_micros)) = (
+                previous.timing.duration_micros,
+                current.timing.duration_micros,
+            ) {
+                let delta = current_micros.saturating_sub(previous_micros);
+                if delta >= DURATION_REGRESSION_MIN_MICROS
+                    && delta as f64 > previous_micros as f64 * regression_ratio
+                {
+                    diff.duration_regressions.push(DurationRegression {
+                        name: name.to_owned(),
+                        previous_micros,
+                        current_micros,
+                    });
+                }
+            }
+        });
+
+        previous_scenarios
+            .iter()
+            .filter(|p| !current_scenarios.iter().any(|c| is_same_scenario(p, c)))
+            .for_each(|(name, _, previous)| {
+                diff.removed.push(ScenarioChange {
+                    name: name.to_owned(),
+                    previous: previous.result.clone(),
+                    current: None,
+                })
+            });
+
+        diff
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum OutputFormat {
+        Json,
+        Cucumber,
+        Junit,
+        Html,
+        Markdown,
+        Allure,
+        Tap,
+        Csv,
+        Prometheus,
+        Trace,
+    }
+
+    impl std::str::FromStr for OutputFormat {
+        type Err = String;
+
+        fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+            match s {
+                "json" => Ok(Self::Json),
+                "cucumber" => Ok(Self::Cucumber),
+                "junit" => Ok(Self::Junit),
+                "html" => Ok(Self::Html),
+                "markdown" => Ok(Self::Markdown),
+                "allure" => Ok(Self::Allure),
+                "tap" => Ok(Self::Tap),
+                "csv" => Ok(Self::Csv),
+                "prometheus" => Ok(Self::Prometheus),
+                "trace" => Ok(Self::Trace),
+                _ => Err(format!("Unknown output format '{}'", s)),
+            }
+        }
+    }
+
+    impl OutputFormat {
+        /// Splits a report parameter such as `cucumber:out/result.json` into its format and
+        /// destination. A bare destination keeps producing the `RunStats` JSON file.
+        pub fn from_output(output: &str) -> (Self, String) {
+            if let Some(index) = output.find(':') {
+                if let Ok(format) = output[..index].parse() {
+                    return (format, output[index + 1..].to_string());
+                }
+            }
+
+            (Self::Json, output.to_string())
+        }
+
+        /// Builds the writer for this format; `pretty` indents the JSON based formats.
+        pub fn writer(&self, path: &str, pretty: bool) -> Box<dyn ReportWriter> {
+            let path = path.to_string();
+            match self {
+                Self::Json => Box::new(JsonWriter { path, pretty }),
+                Self::Cucumber => Box::new(CucumberWriter { path, pretty }),
+                Self::Junit => Box::new(JunitWriter { path }),
+                Self::Html => Box::new(HtmlWriter { path }),
+                Self::Markdown => Box::new(MarkdownWriter { path }),
+                Self::Allure => Box::new(AllureWriter { path }),
+                Self::Tap => Box::new(TapWriter { path }),
+                Self::Csv => Box::new(CsvWriter { path }),
+                Self::Prometheus => Box::new(PrometheusWriter { path }),
+                Self::Trace => Box::new(TraceWriter { path, pretty }),
+            }
+        }
+    }
+
+    /// Produces a report out of the collected `RunStats`.
+    pub trait ReportWriter {
+        fn write(&self, stats: &RunStats) -> Result<()>;
+    }
+
+    /// Prints the feature tree with the failure messages under the failing steps, followed by
+    /// the tag overview and a summary, to stdout.
+    pub struct ConsoleWriter {
+        pub color: ColorMode,
+    }
+
+    impl Default for ConsoleWriter {
+        fn default() -> Self {
+            Self {
+                color: ColorMode::Auto,
+            }
+        }
+    }
+
+    /// Writes the `RunStats` as they are.
+    pub struct JsonWriter {
+        pub path: String,
+        pub pretty: bool,
+    }
+
+    /// Writes the standard Cucumber JSON report.
+    pub struct CucumberWriter {
+        pub path: String,
+        pub pretty: bool,
+    }
+
+    /// Writes a JUnit XML report.
+    pub struct JunitWriter {
+        pub path: String,
+    }
+
+    /// Writes a self-contained HTML report.
+    pub struct HtmlWriter {
+        pub path: String,
+    }
+
+    /// Writes a Markdown summary that can be pasted into pull request comments.
+    pub struct MarkdownWriter {
+        pub path: String,
+    }
+
+    /// Writes an Allure results directory, with one result file per scenario.
+    pub struct AllureWriter {
+        pub path: String,
+    }
+
+    /// Writes a TAP version 13 stream with one test point per scenario.
+    pub struct TapWriter {
+        pub path: String,
+    }
+
+    /// Writes a CSV file with one row per step.
+    pub struct CsvWriter {
+        pub path: String,
+    }
+
+    /// Writes the run as metrics for the node_exporter textfile collector.
+    pub struct PrometheusWriter {
+        pub path: String,
+    }
+
+    /// Writes the run as a Chrome Trace Event file, which chrome://tracing and Perfetto
+    /// show as a timeline.
+    pub struct TraceWriter {
+        pub path: String,
+        pub pretty: bool,
+    }
+
+    impl ReportWriter for ConsoleWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            print_test_results(stats, self.color);
+            Ok(())
+        }
+    }
+
+    impl ReportWriter for JsonWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            write_result_file(&self.path, stats, self.pretty)
+        }
+    }
+
+    impl ReportWriter for CucumberWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            write_report(
+                &self.path,
+                "cucumber.json",
+                &get_cucumber_report(stats, self.pretty)?,
+            )
+        }
+    }
+
+    impl ReportWriter for JunitWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            write_report(&self.path, "junit.xml", &get_junit_report(stats))
+        }
+    }
+
+    impl ReportWriter for HtmlWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            write_report(&self.path, "report.html", &get_html_report(stats))
+        }
+    }
+
+    impl ReportWriter for MarkdownWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            write_report(&self.path, "summary.md", &get_markdown_report(stats))
+        }
+    }
+
+    impl ReportWriter for AllureWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            let directory = format!("{}/", self.path.trim_end_matches('/'));
+            for feature in stats.features.iter() {
+                for scenario in feature.scenarios.iter() {
+                    let (result, attachments) = AllureResult::new(stats, feature, scenario);
+                    for (source, content) in attachments {
+                        write_report(&directory, &source, &content)?;
+                    }
+                    write_report(
+                        &directory,
+                        &format!("{}-result.json", result.uuid),
+                        &serde_json::to_string(&result)?,
+                    )?;
+                }
+            }
+
+            write_report(
+                &directory,
+                "environment.properties",
+                &get_allure_environment(&stats.metadata),
+            )
+        }
+    }
+
+    impl ReportWriter for TapWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            write_report(&self.path, "result.tap", &get_tap_report(stats))
+        }
+    }
+
+    impl ReportWriter for CsvWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            write_report(&self.path, "steps.csv", &get_csv_report(stats))
+        }
+    }
+
+    impl ReportWriter for PrometheusWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            // The collector may read the file at any time, so it's replaced by a rename
+            // from a file it ignores rather than written in place.
+            let target = get_report_target(&self.path, "somebdd.prom")?;
+            let temp = target.with_extension("prom.tmp");
+            std::fs::write(&temp, get_prometheus_report(stats))
+                .map_err(|e| format!("Unable to write report '{}': {}", temp.display(), e))?;
+            std::fs::rename(&temp, &target)
+                .map_err(|e| format!("Unable to write report '{}': {}", target.display(), e))?;
+            Ok(())
+        }
+    }
+
+    impl ReportWriter for TraceWriter {
+        fn write(&self, stats: &RunStats) -> Result<()> {
+            let trace = Trace::new(stats);
+            write_report(&self.path, "trace.json", &get_json(&trace, self.pretty)?)
+        }
+    }
+
+    /// Writes a report to `path`, or to `default_name` inside it when `path` is an existing
+    /// directory or ends with a separator, creating the missing parent directories.
+    fn write_report(path: &str, default_name: &str, content: &str) -> Result<()> {
+        let target = get_report_target(path, default_name)?;
+        std::fs::write(&target, content)
+            .map_err(|e| format!("Unable to write report '{}': {}", target.display(), e))?;
+        Ok(())
+    }
+
+    fn get_report_target(path: &str, default_name: &str) -> Result<std::path::PathBuf> {
+        let mut target = std::path::PathBuf::from(path);
+        if path.ends_with('/') || path.ends_with(std::path::MAIN_SEPARATOR) || target.is_dir() {
+            target.push(default_name);
+        }
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Unable to create directory '{}': {}", parent.display(), e))?;
+        }
+
+        Ok(target)
+    }
+
+    #[derive(Serialize)]
+    struct CucumberFeature {
+        uri: String,
+        id: String,
+        keyword: String,
+        name: String,
+        description: String,
+        line: usize,
+        tags: Vec<CucumberTag>,
+        elements: Vec<CucumberElement>,
+    }
+
+    #[derive(Serialize)]
+    struct CucumberTag {
+        name: String,
+    }
+
+    #[derive(Serialize)]
+    struct CucumberElement {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        keyword: String,
+        #[serde(rename = "type")]
+        element_type: String,
+        name: String,
+        description: String,
+        line: usize,
+        tags: Vec<CucumberTag>,
+        steps: Vec<CucumberStep>,
+    }
+
+    #[derive(Serialize)]
+    struct CucumberStep {
+        keyword: String,
+        name: String,
+        line: usize,
+        result: CucumberStepResult,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        embeddings: Vec<CucumberEmbedding>,
+    }
+
+    #[derive(Serialize)]
+    struct CucumberEmbedding {
+        mime_type: String,
+        data: String,
+    }
+
+    #[derive(Serialize)]
+    struct CucumberStepResult {
+        status: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error_message: Option<String>,
+    }
+
+    impl CucumberFeature {
+        fn new(feature: &FeatureStats) -> Self {
+            let id = get_cucumber_id(&feature.name);
+            Self {
+                uri: feature
+                    .path
+                    .clone()
+                    .unwrap_or_else(|| feature.name.to_owned()),
+                keyword: feature.keyword.to_owned(),
+                name: feature.name.to_owned(),
+                description: feature.description.clone().unwrap_or_default(),
+                line: feature.line,
+                tags: get_cucumber_tags(&feature.tags),
+                elements: feature
+                    .scenarios
+                    .iter()
+                    .flat_map(|s| {
+                        CucumberElement::background(feature, s)
+                            .into_iter()
+                            .chain(std::iter::once(CucumberElement::new(&id, s)))
+                    })
+                    .collect(),
+                id,
+            }
+        }
+    }
+
+    impl CucumberElement {
+        /// Background steps run again for every scenario, so each scenario element is preceded
+        /// by a background element with the results of that run.
+        fn background(feature: &FeatureStats, scenario: &ScenarioStats) -> Option<Self> {
+            let steps: Vec<CucumberStep> = scenario
+                .steps
+                .iter()
+                .filter(|s| s.background)
+                .map(CucumberStep::new)
+                .collect();
+            if steps.is_empty() {
+                return None;
+            }
+
+            // Reports written before the background was recorded don't know its keyword and line.
+            let background = feature.background.clone().unwrap_or(BackgroundStats {
+                keyword: String::from("Background"),
+                line: 0,
+            });
+            Some(Self {
+                id: None,
+                keyword: background.keyword,
+                element_type: String::from("background"),
+                name: String::default(),
+                description: String::default(),
+                line: background.line,
+                tags: Vec::default(),
+                steps,
+            })
+        }
+
+        fn new(feature_id: &str, scenario: &ScenarioStats) -> Self {
+            Self {
+                id: Some(match scenario.example_index {
+                    Some(index) => format!(
+                        "{};{};;{}",
+                        feature_id,
+                        get_cucumber_id(&scenario.name),
+                        index + 2
+                    ),
+                    None => format!("{};{}", feature_id, get_cucumber_id(&scenario.name)),
+                }),
+                keyword: scenario.keyword.to_owned(),
+                element_type: String::from("scenario"),
+                name: scenario.name.to_owned(),
+                description: String::default(),
+                line: scenario.line,
+                tags: get_cucumber_tags(&scenario.tags),
+                steps: scenario
+                    .steps
+                    .iter()
+                    .filter(|s| !s.background)
+                    .map(CucumberStep::new)
+                    .collect(),
+            }
+        }
+    }
+
+    impl CucumberStep {
+        fn new(step: &StepStats) -> Self {
+            let status = match step.result.clone().unwrap_or(StatResult::Skipped) {
+                StatResult::Passed => "passed",
+                StatResult::Failed | StatResult::TimedOut => "failed",
+                StatResult::Skipped => "skipped",
+                StatResult::Undefined => "undefined",
+                StatResult::Pending => "pending",
+                StatResult::Ambiguous => "ambiguous",
+            };
+
+            Self {
+                keyword: format!("{} ", step.keyword),
+                name: step.name.to_owned(),
+                line: step.line,
+                result: CucumberStepResult {
+                    status: status.to_string(),
+                    duration: step.timing.duration_micros.map(|d| d * 1000),
+                    error_message: step.error_message.clone(),
+                },
+                embeddings: step
+                    .attachments
+                    .iter()
+                    .filter_map(|a| serde_json::to_string(a).ok())
+                    .map(|a| CucumberEmbedding {
+                        mime_type: String::from("application/json"),
+                        data: base64::encode(a),
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    fn get_cucumber_tags(tags: &[String]) -> Vec<CucumberTag> {
+        tags.iter()
+            .map(|t| CucumberTag { name: t.to_owned() })
+            .collect()
+    }
+
+    fn get_cucumber_id(name: &str) -> String {
+        name.trim().to_lowercase().replace(' ', "-")
+    }
+
+    fn get_cucumber_report(stats: &RunStats, pretty: bool) -> Result<String> {
+        let features: Vec<CucumberFeature> =
+            stats.features.iter().map(CucumberFeature::new).collect();
+        get_json(&features, pretty)
+    }
+
+    fn get_junit_report(stats: &RunStats) -> String {
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        output += &format!(
+            "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+            stats.total_scenarios,
+            stats.failed_scenarios
+                + stats.undefined_scenarios
+                + stats.ambiguous_scenarios
+                + stats.timed_out_scenarios,
+            stats.skipped_scenarios + stats.pending_scenarios
+        );
+        stats
+            .features
+            .iter()
+            .for_each(|f| output += &get_junit_testsuite(f));
+        output += "</testsuites>\n";
+        output
+    }
+
+    fn get_junit_testsuite(feature: &FeatureStats) -> String {
+        let count = |filter: fn(&StatResult) -> bool| {
+            feature
+                .scenarios
+                .iter()
+                .filter(|s| filter(&s.result.clone().unwrap_or(StatResult::Skipped)))
+                .count()
+        };
+
+        let mut output = format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+            escape_xml(&feature.name),
+            feature.scenarios.len(),
+            count(|r| r.is_failure()),
+            count(|r| *r == StatResult::Skipped || *r == StatResult::Pending),
+            get_junit_time(&feature.timing)
+        );
+
+        feature.scenarios.iter().for_each(|scenario| {
+            output += &format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\">\n",
+                escape_xml(&scenario.get_display_name()),
+                escape_xml(&feature.name),
+                get_junit_time(&scenario.timing)
+            );
+
+            match scenario.result.clone().unwrap_or(StatResult::Skipped) {
+                result if result.is_failure() => scenario
+                    .steps
+                    .iter()
+                    .filter(|st| matches!(&st.result, Some(r) if r.is_failure()))
+                    .for_each(|st| {
+                        // Attributes can't keep line breaks, so the message only holds the first
+                        // line and the whole text goes in the element, before the steps.
+                        let message = st.error_message.clone().unwrap_or_else(|| st.to_string());
+                        output += &format!(
+                            "      <failure message=\"{}\" type=\"{}\">{}</failure>\n",
+                            escape_xml(message.lines().next().unwrap_or_default()),
+                            st.result.clone().unwrap(),
+                            escape_xml(&get_junit_failure(scenario, st))
+                        );
+                    }),
+                StatResult::Passed => {}
+                _ => output += "      <skipped/>\n",
+            }
+
+            // A testcase has at most one system-out and one system-err, so the output captured
+            // by every step is joined, followed by the HTTP exchanges.
+            let get_captured = |captured: fn(&StepStats) -> &Option<String>| {
+                scenario
+                    .steps
+                    .iter()
+                    .filter_map(|st| captured(st).as_ref())
+                    .filter(|c| !c.is_empty())
+                    .map(|c| c.to_owned())
+                    .collect::<Vec<String>>()
+            };
+            let mut system_out = get_captured(|st| &st.captured_stdout);
+            let exchanges = get_exchange_details(scenario);
+            if !exchanges.is_empty() {
+                system_out.push(exchanges);
+            }
+            if !system_out.is_empty() {
+                output += &format!(
+                    "      <system-out>{}</system-out>\n",
+                    escape_xml(&system_out.join("\n"))
+                );
+            }
+            let system_err = get_captured(|st| &st.captured_stderr);
+            if !system_err.is_empty() {
+                output += &format!(
+                    "      <system-err>{}</system-err>\n",
+                    escape_xml(&system_err.join("\n"))
+                );
+            }
+
+            output += "    </testcase>\n";
+        });
+
+        output += "  </testsuite>\n";
+        output
+    }
+
+    fn get_junit_time(timing: &Timing) -> String {
+        format!(
+            "{:.3}",
+            timing.duration_micros.unwrap_or_default() as f64 / 1_000_000.0
+        )
+    }
+
+    /// The HTTP exchanges of every step of the scenario, separated by blank lines.
+    fn get_exchange_details(scenario: &ScenarioStats) -> String {
+        scenario
+            .steps
+            .iter()
+            .flat_map(|st| st.attachments.iter())
+            .map(|a| a.get_details())
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+
+    fn get_junit_failure(scenario: &ScenarioStats, step: &StepStats) -> String {
+        let mut lines: Vec<String> = step.error_message.iter().cloned().collect();
+        lines.extend(scenario.steps.iter().map(|st| st.to_string()));
+        if let Some(location) = &step.failure_location {
+            lines.push(format!("at {}", location));
+        }
+
+        lines.join("\n")
+    }
+
+    const HTML_STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { margin-bottom: 0.2em; }
+.summary { display: flex; gap: 1em; margin: 1em 0; }
+.summary div { padding: 0.6em 1em; border-radius: 4px; background: #eee; }
+details { margin: 0.3em 0 0.3em 1.2em; }
+summary { cursor: pointer; padding: 0.2em 0.4em; border-radius: 3px; }
+.step { margin-left: 2.4em; padding: 0.1em 0.4em; }
+.tag, .rule { font-size: 0.8em; padding: 0 0.4em; border-radius: 3px; background: #dde; }
+.description { margin-left: 1.2em; white-space: pre-wrap; color: #555; }
+.error { margin: 0.2em 0 0.2em 2.4em; padding: 0.4em; background: #fbeaec; white-space: pre-wrap; }
+.exchange { margin-left: 2.4em; font-size: 0.9em; color: #555; }
+.exchange pre { padding: 0.4em; background: #f4f4f4; white-space: pre-wrap; }
+.Passed { background: #d4edda; }
+.Failed { background: #f8d7da; }
+.Skipped, .Pending { background: #fff3cd; }
+.Undefined, .Ambiguous { background: #fde2c4; }
+.TimedOut { background: #f5c6cb; }
+.only-failed .scenario:not(.failure), .only-failed .feature:not(.failure) { display: none; }
+";
+
+    const HTML_SCRIPT: &str = "
+function toggleFailed(checkbox) {
+    document.body.classList.toggle('only-failed', checkbox.checked);
+}
+";
+
+    fn get_html_report(stats: &RunStats) -> String {
+        let mut output =
+            String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        output += "<title>Test results</title>\n";
+        output += &format!("<style>{}</style>\n", HTML_STYLE);
+        output += &format!("<script>{}</script>\n", HTML_SCRIPT);
+        output += "</head>\n<body>\n<h1>Result overview</h1>\n<div class=\"summary\">\n";
+        output += &format!("<div>Total features: {}</div>\n", stats.total_featuress);
+        output += &format!("<div>Total scenarios: {}</div>\n", stats.total_scenarios);
+        output += &format!(
+            "<div class=\"Passed\">Passed scenarios: {}</div>\n",
+            stats.passed_scenarios
+        );
+        output += &format!(
+            "<div class=\"Failed\">Failed scenarios: {}</div>\n",
+            stats.failed_scenarios
+        );
+        output += &format!(
+            "<div class=\"Skipped\">Skipped scenarios: {}</div>\n",
+            stats.skipped_scenarios
+        );
+        output += &format!(
+            "<div class=\"Undefined\">Undefined scenarios: {}</div>\n",
+            stats.undefined_scenarios
+        );
+        output += &format!(
+            "<div class=\"Pending\">Pending scenarios: {}</div>\n",
+            stats.pending_scenarios
+        );
+        output += &format!(
+            "<div class=\"Ambiguous\">Ambiguous scenarios: {}</div>\n",
+            stats.ambiguous_scenarios
+        );
+        output += &format!(
+            "<div class=\"TimedOut\">Timed out scenarios: {}</div>\n",
+            stats.timed_out_scenarios
+        );
+        output += "</div>\n";
+        output += "<label><input type=\"checkbox\" onchange=\"toggleFailed(this)\"> Only failed scenarios</label>\n";
+
+        stats
+            .features
+            .iter()
+            .for_each(|f| output += &get_html_feature(f));
+
+        output += "</body>\n</html>\n";
+        output
+    }
+
+    fn get_html_feature(feature: &FeatureStats) -> String {
+        let result = feature.get_result().unwrap_or(StatResult::Skipped);
+        let mut output = format!(
+            "<details class=\"feature {}{}\" open>\n<summary class=\"{}\" title=\"{}\">{}{}: {}{}</summary>\n",
+            result,
+            get_html_failure_class(&result),
+            result,
+            escape_xml(&feature.get_location(feature.line)),
+            get_html_tags(&feature.tags),
+            escape_xml(&feature.keyword),
+            escape_xml(&feature.name),
+            get_html_duration(&feature.timing)
+        );
+
+        if let Some(description) = &feature.description {
+            output += &format!("<p class=\"description\">{}</p>\n", escape_xml(description));
+        }
+
+        feature.scenarios.iter().for_each(|scenario| {
+            let result = scenario.result.clone().unwrap_or(StatResult::Skipped);
+            output += &format!(
+                "<details class=\"scenario {}{}\"{}>\n<summary class=\"{}\" title=\"{}\">{}{}{}: {}{}</summary>\n",
+                result,
+                get_html_failure_class(&result),
+                if result.is_failure() { " open" } else { "" },
+                result,
+                escape_xml(&feature.get_location(scenario.line)),
+                get_html_tags(&scenario.tags),
+                scenario
+                    .rule
+                    .as_ref()
+                    .map(|r| format!("<span class=\"rule\">Rule: {}</span> ", escape_xml(r)))
+                    .unwrap_or_default(),
+                escape_xml(&scenario.keyword),
+                escape_xml(&scenario.get_display_name()),
+                get_html_duration(&scenario.timing)
+            );
+
+            scenario.steps.iter().for_each(|step| {
+                output += &format!(
+                    "<div class=\"step {}\" title=\"{}\">{}{} {}{}</div>\n",
+                    step.result.clone().unwrap_or(StatResult::Skipped),
+                    escape_xml(&feature.get_location(step.line)),
+                    if step.background {
+                        "<span class=\"rule\">Background</span> "
+                    } else {
+                        ""
+                    },
+                    escape_xml(&step.keyword),
+                    escape_xml(&step.name),
+                    get_html_duration(&step.timing)
+                );
+
+                if let Some(error_message) = &step.error_message {
+                    let location = step
+                        .failure_location
+                        .as_ref()
+                        .map(|l| format!("\nat {}", l))
+                        .unwrap_or_default();
+                    output += &format!(
+                        "<pre class=\"error\">{}{}</pre>\n",
+                        escape_xml(error_message),
+                        escape_xml(&location)
+                    );
+                }
+
+                step.attachments.iter().for_each(|a| {
+                    output += &format!(
+                        "<details class=\"exchange\">\n<summary>{}</summary>\n<pre>{}</pre>\n</details>\n",
+                        escape_xml(&a.to_string()),
+                        escape_xml(&a.get_details())
+                    );
+                });
+            });
+
+            output += "</details>\n";
+        });
+
+        output += "</details>\n";
+        output
+    }
+
+    fn get_html_tags(tags: &[String]) -> String {
+        tags.iter()
+            .map(|t| format!("<span class=\"tag\">{}</span> ", escape_xml(t)))
+            .collect()
+    }
+
+    fn get_html_failure_class(result: &StatResult) -> &'static str {
+        match result.is_failure() {
+            true => " failure",
+            false => "",
+        }
+    }
+
+    fn get_html_duration(timing: &Timing) -> String {
+        match timing.get_duration_millis() {
+            Some(duration) => format!(" <small>({:.3} ms)</small>", duration),
+            None => String::default(),
+        }
+    }
+
+    fn get_markdown_report(stats: &RunStats) -> String {
+        let mut output = String::from("## Test results\n\n");
+        output += "| Features | Scenarios | Passed | Failed | Skipped | Undefined | Pending | Ambiguous | Timed out |\n";
+        output += "| --- | --- | --- | --- | --- | --- | --- | --- | --- |\n";
+        output += &format!(
+            "| {} | {} | {} | {} | {} | {} | {} | {} | {} |\n",
+            stats.total_featuress,
+            stats.total_scenarios,
+            stats.passed_scenarios,
+            stats.failed_scenarios,
+            stats.skipped_scenarios,
+            stats.undefined_scenarios,
+            stats.pending_scenarios,
+            stats.ambiguous_scenarios,
+            stats.timed_out_scenarios
+        );
+
+        let failing: Vec<String> = stats
+            .features
+            .iter()
+            .flat_map(|f| {
+                f.scenarios
+                    .iter()
+                    .filter(|s| matches!(&s.result, Some(r) if r.is_failure()))
+                    .map(move |s| get_markdown_failure(f, s))
+            })
+            .collect();
+        if !failing.is_empty() {
+            output += "\n### Failing scenarios\n\n";
+            failing.iter().for_each(|f| output += f);
+        }
+
+        let passed: Vec<&FeatureStats> = stats
+            .features
+            .iter()
+            .filter(|f| f.get_result() == Some(StatResult::Passed))
+            .collect();
+        if !passed.is_empty() {
+            output += "\n### Passed features\n\n";
+            passed.iter().for_each(|f| {
+                output += &format!(
+                    "- {} ({} scenarios{})\n",
+                    escape_xml(&f.name),
+                    f.scenarios.len(),
+                    get_markdown_duration(&f.timing)
+                )
+            });
+        }
+
+        output
+    }
+
+    fn get_markdown_failure(feature: &FeatureStats, scenario: &ScenarioStats) -> String {
+        let mut output = format!(
+            "<details>\n<summary>{} / {}: {}</summary>\n\n",
+            escape_xml(&feature.name),
+            escape_xml(&scenario.get_display_name()),
+            scenario.result.as_ref().unwrap()
+        );
+        output += &format!(
+            "Scenario at {}\n",
+            get_markdown_code(&feature.get_location(scenario.line))
+        );
+
+        let step = scenario
+            .steps
+            .iter()
+            .find(|st| matches!(&st.result, Some(r) if r.is_failure()));
+        if let Some(step) = step {
+            output += &format!(
+                "\nFailing step: {} ({})\n",
+                get_markdown_code(&format!("{} {}", step.keyword.trim(), step.name)),
+                feature.get_location(step.line)
+            );
+            if let Some(message) = &step.error_message {
+                output += &format!("\n{}", get_markdown_code_block(message));
+            }
+            if let Some(location) = &step.failure_location {
+                output += &format!("\nat {}\n", get_markdown_code(location));
+            }
+        }
+
+        let exchanges = get_exchange_details(scenario);
+        if !exchanges.is_empty() {
+            output += &format!(
+                "\nHTTP exchanges:\n\n{}",
+                get_markdown_code_block(&exchanges)
+            );
+        }
+
+        output += "\n</details>\n\n";
+        output
+    }
+
+    /// Length of the longest run of backticks in `text`, which a code span or fence around it
+    /// has to exceed.
+    fn get_longest_backtick_run(text: &str) -> usize {
+        text.split(|c| c != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Wraps `text` in an inline code span that its own backticks cannot close.
+    fn get_markdown_code(text: &str) -> String {
+        let fence = "`".repeat(get_longest_backtick_run(text) + 1);
+        match text.starts_with('`') || text.ends_with('`') {
+            true => format!("{} {} {}", fence, text, fence),
+            false => format!("{}{}{}", fence, text, fence),
+        }
+    }
+
+    /// Wraps `text` in a fenced code block that its own backticks cannot close.
+    fn get_markdown_code_block(text: &str) -> String {
+        let fence = "`".repeat(std::cmp::max(3, get_longest_backtick_run(text) + 1));
+        format!("{}\n{}\n{}\n", fence, text, fence)
+    }
+
+    fn get_markdown_duration(timing: &Timing) -> String {
+        match timing.get_duration_millis() {
+            Some(duration) => format!(", {:.3} ms", duration),
+            None => String::new(),
+        }
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct AllureResult {
+        uuid: String,
+        history_id: String,
+        full_name: String,
+        name: String,
+        status: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        status_details: Option<AllureStatusDetails>,
+        stage: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        start: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stop: Option<u64>,
+        labels: Vec<AllureLabel>,
+        steps: Vec<AllureStep>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct AllureStep {
+        name: String,
+        status: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        status_details: Option<AllureStatusDetails>,
+        stage: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        start: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stop: Option<u64>,
+        attachments: Vec<AllureAttachment>,
+    }
+
+    #[derive(Serialize)]
+    struct AllureStatusDetails {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        trace: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct AllureLabel {
+        name: String,
+        value: String,
+    }
+
+    #[derive(Serialize)]
+    struct AllureAttachment {
+        name: String,
+        source: String,
+        #[serde(rename = "type")]
+        attachment_type: String,
+    }
+
+    impl AllureResult {
+        /// Builds the result of a scenario along with the files of its attachments, as pairs of
+        /// file name and content.
+        fn new(
+            stats: &RunStats,
+            feature: &FeatureStats,
+            scenario: &ScenarioStats,
+        ) -> (Self, Vec<(String, String)>) {
+            let full_name = format!("{}: {}", feature.name, scenario.get_display_name());
+            let uuid = get_allure_uuid(&format!(
+                "{}{:?}{:?}",
+                full_name, scenario.line, scenario.timing.start_micros
+            ));
+            let mut attachments = Vec::default();
+
+            let steps = scenario
+                .steps
+                .iter()
+                .enumerate()
+                .map(|(step_index, step)| AllureStep {
+                    name: format!("{} {}", step.keyword.trim(), step.name),
+                    status: get_allure_status(&step.result).to_string(),
+                    status_details: get_allure_status_details(step),
+                    stage: String::from("finished"),
+                    start: step.timing.start_micros.map(|t| t / 1000),
+                    stop: step.timing.end_micros.map(|t| t / 1000),
+                    attachments: step
+                        .attachments
+                        .iter()
+                        .enumerate()
+                        .map(|(index, exchange)| {
+                            let source = format!(
+                                "{}-attachment.txt",
+                                get_allure_uuid(&format!("{}{}{}", uuid, step_index, index))
+                            );
+                            attachments.push((source.to_owned(), exchange.get_details()));
+                            AllureAttachment {
+                                name: format!("{} {}", exchange.method, exchange.url),
+                                source,
+                                attachment_type: String::from("text/plain"),
+                            }
+                        })
+                        .collect(),
+                })
+                .collect();
+
+            let result = Self {
+                history_id: get_allure_uuid(&full_name),
+                uuid,
+                name: scenario.get_display_name(),
+                full_name,
+                status: get_allure_status(&scenario.result).to_string(),
+                status_details: scenario
+                    .steps
+                    .iter()
+                    .find(|st| matches!(&st.result, Some(r) if r.is_failure()))
+                    .and_then(get_allure_status_details),
+                stage: String::from("finished"),
+                start: scenario.timing.start_micros.map(|t| t / 1000),
+                stop: scenario.timing.end_micros.map(|t| t / 1000),
+                labels: get_allure_labels(stats, feature, scenario),
+                steps,
+            };
+
+            (result, attachments)
+        }
+    }
+
+    fn get_allure_labels(
+        stats: &RunStats,
+        feature: &FeatureStats,
+        scenario: &ScenarioStats,
+    ) -> Vec<AllureLabel> {
+        let label = |name: &str, value: &str| AllureLabel {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+
+        let mut labels = vec![
+            label("feature", &feature.name),
+            label("suite", &feature.name),
+            label("framework", "cucumber_rust"),
+            label("language", "rust"),
+        ];
+        if let Some(rule) = &scenario.rule {
+            labels.push(label("subSuite", rule));
+        }
+        if let Some(hostname) = &stats.metadata.hostname {
+            labels.push(label("host", hostname));
+        }
+        scenario
+            .tags
+            .iter()
+            .for_each(|t| labels.push(label("tag", t.trim_start_matches('@'))));
+        labels
+    }
+
+    /// Allure tells assertion failures (`failed`) apart from the runs that couldn't check
+    /// anything (`broken`).
+    fn get_allure_status(result: &Option<StatResult>) -> &'static str {
+        match result.clone().unwrap_or(StatResult::Skipped) {
+            StatResult::Passed => "passed",
+            StatResult::Failed => "failed",
+            StatResult::Undefined | StatResult::Ambiguous | StatResult::TimedOut => "broken",
+            StatResult::Skipped | StatResult::Pending => "skipped",
+        }
+    }
+
+    fn get_allure_status_details(step: &StepStats) -> Option<AllureStatusDetails> {
+        step.error_message
+            .as_ref()
+            .map(|message| AllureStatusDetails {
+                message: message.to_owned(),
+                trace: step.failure_location.as_ref().map(|l| format!("at {}", l)),
+            })
+    }
+
+    /// A UUID shaped identifier derived from `seed`, so results keep their ids when written again.
+    fn get_allure_uuid(seed: &str) -> String {
+        use std::hash::{Hash, Hasher};
+
+        let hash = |salt: u8| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            salt.hash(&mut hasher);
+            seed.hash(&mut hasher);
+            hasher.finish()
+        };
+        let value = format!("{:016x}{:016x}", hash(0), hash(1));
+        format!(
+            "{}-{}-{}-{}-{}",
+            &value[..8],
+            &value[8..12],
+            &value[12..16],
+            &value[16..20],
+            &value[20..]
+        )
+    }
+
+    fn get_allure_environment(metadata: &RunMetadata) -> String {
+        let mut properties = Vec::default();
+        if let Some(api_host) = &metadata.api_host {
+            properties.push(format!("api_host={}", api_host));
+        }
+        if let Some(hostname) = &metadata.hostname {
+            properties.push(format!("hostname={}", hostname));
+        }
+        if let Some(git_commit) = &metadata.git_commit {
+            properties.push(format!("git_commit={}", git_commit));
+        }
+        metadata
+            .labels
+            .iter()
+            .for_each(|(name, value)| properties.push(format!("{}={}", name, value)));
+
+        properties.join("\n") + "\n"
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Trace {
+        trace_events: Vec<TraceEvent>,
+        display_time_unit: String,
+    }
+
+    /// A complete (`X`) event spans `ts` to `ts + dur` and nests within the events of the
+    /// same thread that enclose it; a metadata (`M`) event names the process or a thread.
+    #[derive(Serialize)]
+    struct TraceEvent {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cat: Option<String>,
+        ph: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ts: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        dur: Option<u64>,
+        pid: u32,
+        tid: usize,
+        args: serde_json::Value,
+    }
+
+    impl Trace {
+        /// Every feature gets its own thread, so that the features of merged reports,
+        /// which ran at the same time on different shards, don't overlap on the timeline.
+        fn new(stats: &RunStats) -> Self {
+            let process_name = match &stats.metadata.api_host {
+                Some(api_host) => format!("somebdd ({})", api_host),
+                None => String::from("somebdd"),
+            };
+            let mut trace_events = vec![TraceEvent::metadata(
+                "process_name",
+                0,
+                serde_json::json!({ "name": process_name }),
+            )];
+
+            stats
+                .features
+                .iter()
+                .enumerate()
+                .for_each(|(index, feature)| {
+                    let tid = index + 1;
+                    trace_events.push(TraceEvent::metadata(
+                        "thread_name",
+                        tid,
+                        serde_json::json!({ "name": feature.name }),
+                    ));
+                    trace_events.extend(TraceEvent::complete(
+                        &feature.name,
+                        "feature",
+                        tid,
+                        &feature.timing,
+                        serde_json::json!({ "location": feature.path, "result": get_trace_result(&feature.get_result()) }),
+                    ));
+
+                    feature.scenarios.iter().for_each(|scenario| {
+                        trace_events.extend(TraceEvent::complete(
+                            &scenario.get_display_name(),
+                            "scenario",
+                            tid,
+                            &scenario.timing,
+                            serde_json::json!({
+                                "location": feature.get_location(scenario.line),
+                                "result": get_trace_result(&scenario.result),
+                                "tags": scenario.tags,
+                            }),
+                        ));
+
+                        scenario.steps.iter().for_each(|step| {
+                            trace_events.extend(TraceEvent::complete(
+                                &format!("{} {}", step.keyword.trim(), step.name),
+                                "step",
+                                tid,
+                                &step.timing,
+                                serde_json::json!({
+                                    "location": feature.get_location(step.line),
+                                    "result": get_trace_result(&step.result),
+                                    "error_message": step.error_message,
+                                }),
+                            ));
+
+                            step.attachments.iter().for_each(|exchange| {
+                                trace_events.extend(TraceEvent::complete(
+                                    &format!("{} {}", exchange.method, exchange.url),
+                                    "http",
+                                    tid,
+                                    &exchange.timing,
+                                    serde_json::json!({ "status": exchange.status, "error": exchange.error }),
+                                ));
+                            });
+                        });
+                    });
+                });
+
+            Self {
+                trace_events,
+                display_time_unit: String::from("ms"),
+            }
+        }
+    }
+
+    impl TraceEvent {
+        fn metadata(name: &str, tid: usize, args: serde_json::Value) -> Self {
+            Self {
+                name: name.to_string(),
+                cat: None,
+                ph: String::from("M"),
+                ts: None,
+                dur: None,
+                pid: 1,
+                tid,
+                args,
+            }
+        }
+
+        /// Elements that didn't run have no timing and are left out of the timeline.
+        fn complete(
+            name: &str,
+            cat: &str,
+            tid: usize,
+            timing: &Timing,
+            args: serde_json::Value,
+        ) -> Option<Self> {
+            let start = timing.start_micros?;
+            let end = timing.end_micros?;
+            Some(Self {
+                name: name.to_string(),
+                cat: Some(cat.to_string()),
+                ph: String::from("X"),
+                ts: Some(start),
+                dur: Some(end.saturating_sub(start)),
+                pid: 1,
+                tid,
+                args,
+            })
+        }
+    }
+
+    fn get_trace_result(result: &Option<StatResult>) -> String {
+        result.clone().unwrap_or(StatResult::Skipped).to_string()
+    }
+
+    fn get_tap_report(stats: &RunStats) -> String {
+        let scenarios: Vec<(&FeatureStats, &ScenarioStats)> = stats
+            .features
+            .iter()
+            .flat_map(|f| f.scenarios.iter().map(move |s| (f, s)))
+            .collect();
+
+        let mut output = format!("TAP version 13\n1..{}\n", scenarios.len());
+        scenarios
+            .iter()
+            .enumerate()
+            .for_each(|(index, (feature, scenario))| {
+                let result = scenario.result.clone().unwrap_or(StatResult::Skipped);
+                let description = format!("{}: {}", feature.name, scenario.get_display_name())
+                    .replace('#', "\\#");
+                output += &match result {
+                    StatResult::Passed => format!("ok {} - {}\n", index + 1, description),
+                    StatResult::Skipped => format!("ok {} - {} # SKIP\n", index + 1, description),
+                    StatResult::Pending => {
+                        format!("not ok {} - {} # TODO pending\n", index + 1, description)
+                    }
+                    _ => format!("not ok {} - {}\n", index + 1, description),
+                };
+
+                if result.is_failure() {
+                    output += &get_tap_diagnostics(feature, scenario, &result);
+                }
+            });
+
+        output
+    }
+
+    fn get_tap_diagnostics(
+        feature: &FeatureStats,
+        scenario: &ScenarioStats,
+        result: &StatResult,
+    ) -> String {
+        let mut output = String::from("  ---\n");
+        output += &format!("  status: {}\n", result);
+        output += &format!("  location: {}\n", feature.get_location(scenario.line));
+        if let Some(duration) = scenario.timing.get_duration_millis() {
+            output += &format!("  duration_ms: {:.3}\n", duration);
+        }
+
+        let step = scenario
+            .steps
+            .iter()
+            .find(|st| matches!(&st.result, Some(r) if r.is_failure()));
+        if let Some(step) = step {
+            output += &format!(
+                "  step: \"{} {}\"\n",
+                step.keyword.trim(),
+                step.name.replace('\\', "\\\\").replace('"', "\\\"")
+            );
+            if let Some(message) = &step.error_message {
+                output += "  message: |\n";
+                message
+                    .lines()
+                    .for_each(|line| output += &format!("    {}\n", line));
+            }
+            if let Some(location) = &step.failure_location {
+                output += &format!("  at: {}\n", location);
+            }
+        }
+
+        let exchanges = get_exchange_details(scenario);
+        if !exchanges.is_empty() {
+            output += "  exchanges: |\n";
+            exchanges
+                .lines()
+                .for_each(|line| output += &format!("    {}\n", line));
+        }
+
+        output += "  ...\n";
+        output
+    }
+
+    fn get_csv_report(stats: &RunStats) -> String {
+        let mut output = String::from("feature,scenario,keyword,step,status,duration_ms\n");
+        stats.features.iter().for_each(|feature| {
+            feature.scenarios.iter().for_each(|scenario| {
+                scenario.steps.iter().for_each(|step| {
+                    let fields = [
+                        feature.name.to_owned(),
+                        scenario.get_display_name(),
+                        step.keyword.trim().to_string(),
+                        step.name.to_owned(),
+                        step.result
+                            .clone()
+                            .unwrap_or(StatResult::Skipped)
+                            .to_string(),
+                        step.timing
+                            .get_duration_millis()
+                            .map(|d| format!("{:.3}", d))
+                            .unwrap_or_default(),
+                    ];
+                    output += &fields
+                        .iter()
+                        .map(|f| escape_csv(f))
+                        .collect::<Vec<String>>()
+                        .join(",");
+                    output += "\n";
+                });
+            });
+        });
+
+        output
+    }
+
+    fn escape_csv(value: &str) -> String {
+        match value.contains(&[',', '"', '\n', '\r'][..]) {
+            true => format!("\"{}\"", value.replace('"', "\"\"")),
+            false => value.to_string(),
+        }
+    }
+
+    fn get_prometheus_report(stats: &RunStats) -> String {
+        let mut output = String::new();
+        output += "# HELP somebdd_scenarios Number of scenarios of the last run by result.\n";
+        output += "# TYPE somebdd_scenarios gauge\n";
+        [
+            ("passed", stats.passed_scenarios),
+            ("failed", stats.failed_scenarios),
+            ("skipped", stats.skipped_scenarios),
+            ("undefined", stats.undefined_scenarios),
+            ("pending", stats.pending_scenarios),
+            ("ambiguous", stats.ambiguous_scenarios),
+            ("timed_out", stats.timed_out_scenarios),
+        ]
+        .iter()
+        .for_each(|(result, count)| {
+            output += &format!("somebdd_scenarios{{result=\"{}\"}} {}\n", result, count);
+        });
+
+        output += "# HELP somebdd_scenario_success Whether the scenario passed in the last run.\n";
+        output += "# TYPE somebdd_scenario_success gauge\n";
+        stats.features.iter().for_each(|feature| {
+            feature.scenarios.iter().for_each(|scenario| {
+                let success = matches!(scenario.result, Some(StatResult::Passed)) as u8;
+                output += &format!(
+                    "somebdd_scenario_success{{{}}} {}\n",
+                    get_prometheus_labels(feature, scenario),
+                    success
+                );
+            });
+        });
+
+        output += "# HELP somebdd_step_duration_seconds Duration of the step in the last run.\n";
+        output += "# TYPE somebdd_step_duration_seconds gauge\n";
+        stats.features.iter().for_each(|feature| {
+            feature.scenarios.iter().for_each(|scenario| {
+                scenario.steps.iter().for_each(|step| {
+                    if let Some(duration) = step.timing.duration_micros {
+                        output += &format!(
+                            "somebdd_step_duration_seconds{{{},step=\"{}\",step_line=\"{}\"}} {}\n",
+                            get_prometheus_labels(feature, scenario),
+                            escape_prometheus_label(&format!(
+                                "{} {}",
+                                step.keyword.trim(),
+                                step.name
+                            )),
+                            step.line,
+                            duration as f64 / 1_000_000.0
+                        );
+                    }
+                });
+            });
+        });
+
+        let timestamp = stats
+            .metadata
+            .timing
+            .end_micros
+            .unwrap_or_else(get_now_micros);
+        output += "# HELP somebdd_last_run_timestamp_seconds When the last run finished.\n";
+        output += "# TYPE somebdd_last_run_timestamp_seconds gauge\n";
+        output += &format!(
+            "somebdd_last_run_timestamp_seconds {}\n",
+            timestamp as f64 / 1_000_000.0
+        );
+
+        output
+    }
+
+    /// Scenario names aren't unique, and neither are the names of outline rows, so the
+    /// line and the example values are part of the labels.
+    fn get_prometheus_labels(feature: &FeatureStats, scenario: &ScenarioStats) -> String {
+        format!(
+            "feature=\"{}\",scenario=\"{}\",line=\"{}\"",
+            escape_prometheus_label(&feature.name),
+            escape_prometheus_label(&scenario.get_display_name()),
+            scenario.line
+        )
+    }
+
+    fn escape_prometheus_label(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    }
+
+    fn escape_xml(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&apos;")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn step(ty: gherkin::StepType, value: &str) -> gherkin::Step {
+            gherkin::Step {
+                keyword: String::from("Given"),
+                ty,
+                value: value.to_string(),
+                docstring: None,
+                table: None,
+                span: Default::default(),
+                position: Default::default(),
+            }
+        }
+
+        fn scenario(name: &str, line: usize, result: StatResult) -> ScenarioStats {
+            ScenarioStats {
+                name: name.to_string(),
+                line,
+                result: Some(result),
+                ..ScenarioStats::default()
+            }
+        }
+
+        fn timed_scenario(line: usize, duration_micros: u64) -> ScenarioStats {
+            ScenarioStats {
+                timing: Timing {
+                    start_micros: Some(0),
+                    end_micros: Some(duration_micros),
+                    duration_micros: Some(duration_micros),
+                },
+                ..scenario("Timed", line, StatResult::Passed)
+            }
+        }
+
+        fn feature(name: &str, path: &str, scenarios: Vec<ScenarioStats>) -> FeatureStats {
+            FeatureStats {
+                name: name.to_string(),
+                line: 1,
+                path: Some(path.to_string()),
+                scenarios,
+                ..FeatureStats::default()
+            }
+        }
+
+        fn run(scenarios: Vec<ScenarioStats>) -> RunStats {
+            RunStats::from_features(vec![feature("Orders", "orders.feature", scenarios)])
+        }
+
+        #[test]
+        fn diff_reports_newly_failing_scenario_with_duplicate_name() {
+            let previous = run(vec![
+                scenario("Same", 3, StatResult::Failed),
+                scenario("Same", 6, StatResult::Passed),
+            ]);
+            let current = run(vec![
+                scenario("Same", 3, StatResult::Failed),
+                scenario("Same", 6, StatResult::Failed),
+            ]);
+
+            let diff = diff_results(&previous, &current, 0.5);
+
+            assert_eq!(diff.newly_failing.len(), 1);
+            assert!(diff.newly_failing[0].previous == Some(StatResult::Passed));
+            assert!(diff.has_new_failures());
+        }
+
+        #[test]
+        fn diff_reports_newly_passing_scenario() {
+            let previous = run(vec![scenario("Orders", 3, StatResult::Failed)]);
+            let current = run(vec![scenario("Orders", 3, StatResult::Passed)]);
+
+            let diff = diff_results(&previous, &current, 0.5);
+
+            assert_eq!(diff.newly_passing.len(), 1);
+            assert!(diff.newly_failing.is_empty());
+            assert!(!diff.has_new_failures());
+        }
+
+        #[test]
+        fn diff_reports_added_failing_scenario_as_new_failure() {
+            let previous = run(vec![scenario("Orders", 3, StatResult::Passed)]);
+            let current = run(vec![
+                scenario("Orders", 3, StatResult::Passed),
+                scenario("Orders", 9, StatResult::Failed),
+            ]);
+
+            let diff = diff_results(&previous, &current, 0.5);
+
+            assert_eq!(diff.added.len(), 1);
+            assert!(diff.newly_failing.is_empty());
+            assert!(diff.has_new_failures());
+        }
+
+        #[test]
+        fn diff_reports_removed_scenario() {
+            let previous = run(vec![
+                scenario("Orders", 3, StatResult::Passed),
+                scenario("Orders", 9, StatResult::Failed),
+            ]);
+            let current = run(vec![scenario("Orders", 3, StatResult::Passed)]);
+
+            let diff = diff_results(&previous, &current, 0.5);
+
+            assert_eq!(diff.removed.len(), 1);
+            assert!(diff.removed[0].previous == Some(StatResult::Failed));
+            assert!(!diff.has_new_failures());
+        }
+
+        #[test]
+        fn diff_ignores_duration_regressions_below_the_minimum() {
+            let min = DURATION_REGRESSION_MIN_MICROS;
+            let previous = run(vec![timed_scenario(3, min), timed_scenario(6, min)]);
+            let current = run(vec![
+                timed_scenario(3, min + min / 2 + 1),
+                timed_scenario(6, min * 3),
+            ]);
+
+            let diff = diff_results(&previous, &current, 0.5);
+
+            assert_eq!(diff.duration_regressions.len(), 1);
+            assert_eq!(diff.duration_regressions[0].current_micros, min * 3);
+        }
+
+        fn finished_scenario(result: StatResult, end_micros: u64) -> ScenarioStats {
+            ScenarioStats {
+                timing: Timing {
+                    start_micros: Some(0),
+                    end_micros: Some(end_micros),
+                    duration_micros: Some(end_micros),
+                },
+                ..scenario("Orders", 3, result)
+            }
+        }
+
+        #[test]
+        fn merge_keeps_features_with_the_same_name_from_different_files() {
+            let first = RunStats::from_features(vec![feature(
+                "Same",
+                "a.feature",
+                vec![scenario("Same", 3, StatResult::Passed)],
+            )]);
+            let second = RunStats::from_features(vec![feature(
+                "Same",
+                "b.feature",
+                vec![scenario("Same", 3, StatResult::Failed)],
+            )]);
+
+            let merged = RunStats::merge(&[first, second]);
+
+            assert_eq!(merged.features.len(), 2);
+            assert_eq!(merged.total_scenarios, 2);
+            assert_eq!(merged.failed_scenarios, 1);
+        }
+
+        #[test]
+        fn merge_keeps_the_scenario_that_finished_last() {
+            let first = run(vec![finished_scenario(StatResult::Failed, 200)]);
+            let second = run(vec![finished_scenario(StatResult::Passed, 100)]);
+
+            let merged = RunStats::merge(&[first, second]);
+
+            assert_eq!(merged.total_scenarios, 1);
+            assert!(merged.features[0].scenarios[0].result == Some(StatResult::Failed));
+        }
+
+        #[test]
+        fn merge_keeps_the_scenario_of_the_last_report_on_equal_end_times() {
+            let first = run(vec![finished_scenario(StatResult::Failed, 100)]);
+            let second = run(vec![finished_scenario(StatResult::Passed, 100)]);
+
+            let merged = RunStats::merge(&[first, second]);
+
+            assert_eq!(merged.total_scenarios, 1);
+            assert!(merged.features[0].scenarios[0].result == Some(StatResult::Passed));
+        }
+
+        fn load_report(name: &str, report: serde_json::Value) -> Result<RunStats> {
+            let path =
+                std::env::temp_dir().join(format!("somebdd-{}-{}.json", name, get_now_micros()));
+            std::fs::write(&path, report.to_string()).unwrap();
+            let stats = RunStats::load(&path.to_string_lossy());
+            std::fs::remove_file(&path).unwrap();
+            stats
+        }
+
+        #[test]
+        fn load_reads_reports_without_schema_version() {
+            let stats = load_report(
+                "v0",
+                serde_json::json!({
+                    "total_featuress": 1,
+                    "total_scenarios": 2,
+                    "skipped_scenarios": 0,
+                    "passed_scenarios": 1,
+                    "failed_scenarios": 1,
+                    "features": [{
+                        "name": "Orders",
+                        "scenarios": [{
+                            "name": "Open orders",
+                            "steps": [{ "name": "request is authenticated", "keyword": "Given ", "result": "Passed" }],
+                            "result": "Passed"
+                        }, {
+                            "name": "Closed orders",
+                            "steps": [{ "name": "request is authenticated", "keyword": "Given ", "result": "Failed" }],
+                            "result": "Failed"
+                        }]
+                    }]
+                }),
+            )
+            .unwrap();
+
+            assert_eq!(stats.schema_version, SCHEMA_VERSION);
+            assert_eq!(stats.total_featuress, 1);
+            assert_eq!(stats.total_scenarios, 2);
+            assert_eq!(stats.passed_scenarios, 1);
+            assert_eq!(stats.failed_scenarios, 1);
+            assert_eq!(stats.features[0].scenarios[1].line, 0);
+            assert!(stats.features[0].scenarios[0]
+                .timing
+                .duration_micros
+                .is_none());
+        }
+
+        fn versioned_report(version: u32) -> serde_json::Value {
+            serde_json::json!({
+                "schema_version": version,
+                "total_featuress": 1,
+                "total_scenarios": 1,
+                "skipped_scenarios": 0,
+                "passed_scenarios": 1,
+                "failed_scenarios": 0,
+                "undefined_scenarios": 0,
+                "pending_scenarios": 0,
+                "ambiguous_scenarios": 0,
+                "timed_out_scenarios": 0,
+                "features": [{
+                    "name": "Orders",
+                    "keyword": "Feature",
+                    "line": 2,
+                    "path": "features/orders.feature",
+                    "description": null,
+                    "tags": ["@private"],
+                    "scenarios": [{
+                        "name": "Open orders",
+                        "keyword": "Scenario",
+                        "line": 4,
+                        "tags": ["@private"],
+                        "rule": null,
+                        "example_index": null,
+                        "example_values": null,
+                        "steps": [{
+                            "name": "request is authenticated",
+                            "keyword": "Given ",
+                            "line": 5,
+                            "background": false,
+                            "result": "Passed",
+                            "timing": { "start_micros": 10, "end_micros": 20, "duration_micros": 10 },
+                            "error_message": null,
+                            "failure_location": null,
+                            "captured_stdout": null,
+                            "captured_stderr": null
+                        }],
+                        "result": "Passed",
+                        "timing": { "start_micros": 10, "end_micros": 20, "duration_micros": 10 }
+                    }],
+                    "timing": { "start_micros": 10, "end_micros": 20, "duration_micros": 10 }
+                }],
+                "tags": [{
+                    "name": "@private",
+                    "total_scenarios": 1,
+                    "passed_scenarios": 1,
+                    "failed_scenarios": 0,
+                    "skipped_scenarios": 0,
+                    "duration_micros": 10
+                }]
+            })
+        }
+
+        #[test]
+        fn load_reads_version_1_reports_without_metadata() {
+            let stats = load_report("v1", versioned_report(1)).unwrap();
+
+            assert_eq!(stats.schema_version, SCHEMA_VERSION);
+            assert_eq!(stats.passed_scenarios, 1);
+            assert_eq!(stats.tags[0].name, "@private");
+            assert!(stats.metadata.api_host.is_none());
+        }
+
+        #[test]
+        fn load_reads_version_2_reports_without_attachments() {
+            let mut report = versioned_report(2);
+            report["metadata"] = serde_json::json!({
+                "timing": { "start_micros": 10, "end_micros": 20, "duration_micros": 10 },
+                "api_host": "api.kraken.com",
+                "hostname": "runner",
+                "feature_paths": ["./features"],
+                "tag_filters": [],
+                "git_commit": null,
+                "labels": { "environment": "staging" }
+            });
+
+            let stats = load_report("v2", report).unwrap();
+
+            assert_eq!(stats.schema_version, SCHEMA_VERSION);
+            assert_eq!(stats.metadata.api_host.as_deref(), Some("api.kraken.com"));
+            assert_eq!(stats.metadata.labels["environment"], "staging");
+            assert!(stats.features[0].scenarios[0].steps[0]
+                .attachments
+                .is_empty());
+        }
+
+        #[test]
+        fn load_rejects_newer_schema_versions() {
+            let result = load_report("newer", versioned_report(SCHEMA_VERSION + 1));
+
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn event_stream_appends_to_an_existing_file() {
+            let path =
+                std::env::temp_dir().join(format!("somebdd-events-{}.ndjson", get_now_micros()));
+            let destination = path.to_string_lossy().into_owned();
+            for _ in 0..2 {
+                let mut handler = EventStreamHandler::new(&destination).unwrap();
+                handler.write_event(StreamEvent::new("run_started"));
+            }
+
+            let content = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(content.lines().count(), 2);
+        }
+
+        #[test]
+        fn tag_locations_are_found_on_the_tag_lines_above_an_element() {
+            let source = "@public\r\n@smoke  @slow # nightly\r\nFeature: Orders\r\n\r\n    # wip\r\n    @private\r\n    Scenario: Open orders\r\n";
+
+            let tag = |line: usize, column: usize, name: &str| (line, column, name.to_string());
+            assert_eq!(
+                get_tag_locations(source, 3),
+                vec![
+                    tag(1, 1, "@public"),
+                    tag(2, 1, "@smoke"),
+                    tag(2, 9, "@slow")
+                ]
+            );
+            assert_eq!(get_tag_locations(source, 7), vec![tag(6, 5, "@private")]);
+            assert!(get_tag_locations("", 7).is_empty());
+        }
+
+        #[test]
+        fn step_matching_several_patterns_is_ambiguous() {
+            let handler = RunEventHandler::with_step_patterns(&[
+                (gherkin::StepType::When, "(.*) is requested"),
+                (gherkin::StepType::When, "system (.*)"),
+                (gherkin::StepType::Then, "system time is requested"),
+            ]);
+
+            let patterns = handler
+                .get_matching_patterns(&step(gherkin::StepType::When, "system time is requested"));
+
+            assert_eq!(patterns, vec!["'(.*) is requested'", "'system (.*)'"]);
+        }
+
+        #[test]
+        fn step_matching_a_literal_is_not_ambiguous() {
+            let handler = RunEventHandler::with_step_patterns(&[
+                (gherkin::StepType::When, "(.*) is requested"),
+                (gherkin::StepType::When, "system (.*)"),
+            ])
+            .with_step_literals(&[(gherkin::StepType::When, "system time is requested")]);
+
+            let patterns = handler
+                .get_matching_patterns(&step(gherkin::StepType::When, "system time is requested"));
+
+            assert!(patterns.is_empty());
+        }
+
+        #[test]
+        fn cucumber_report_puts_background_steps_in_their_own_element() {
+            let background_step = StepStats {
+                name: String::from("request is authenticated"),
+                keyword: String::from("Given"),
+                line: 4,
+                background: true,
+                result: Some(StatResult::Passed),
+                ..StepStats::default()
+            };
+            let scenario_step = StepStats {
+                name: String::from("all current open orders are requested"),
+                keyword: String::from("When"),
+                line: 7,
+                result: Some(StatResult::Passed),
+                ..StepStats::default()
+            };
+            let feature = FeatureStats {
+                background: Some(BackgroundStats {
+                    keyword: String::from("Background"),
+                    line: 3,
+                }),
+                ..feature(
+                    "Orders",
+                    "orders.feature",
+                    vec![ScenarioStats {
+                        steps: vec![background_step, scenario_step],
+                        ..scenario("Open orders", 6, StatResult::Passed)
+                    }],
+                )
+            };
+
+            let report = serde_json::to_value(CucumberFeature::new(&feature)).unwrap();
+
+            let elements = report["elements"].as_array().unwrap();
+            assert_eq!(elements.len(), 2);
+            assert_eq!(elements[0]["type"], "background");
+            assert_eq!(elements[0]["line"], 3);
+            assert!(elements[0].get("id").is_none());
+            assert_eq!(elements[0]["steps"][0]["line"], 4);
+            assert_eq!(elements[1]["type"], "scenario");
+            assert_eq!(elements[1]["steps"].as_array().unwrap().len(), 1);
+            assert_eq!(elements[1]["steps"][0]["line"], 7);
+        }
+
+        fn failed_step(line: usize, message: &str) -> StepStats {
+            StepStats {
+                name: String::from("gets successful response as json"),
+                keyword: String::from("Then"),
+                line,
+                result: Some(StatResult::Failed),
+                error_message: Some(message.to_string()),
+                captured_stdout: Some(format!("stdout of line {}", line)),
+                captured_stderr: Some(format!("stderr of line {}", line)),
+                ..StepStats::default()
+            }
+        }
+
+        #[test]
+        fn junit_report_keeps_one_line_failure_messages_and_one_output_per_testcase() {
+            let stats = run(vec![ScenarioStats {
+                steps: vec![
+                    failed_step(5, "request was successful:\n\texpected: <true>"),
+                    failed_step(6, "response is json"),
+                ],
+                ..scenario("Open orders", 4, StatResult::Failed)
+            }]);
+
+            let report = get_junit_report(&stats);
+
+            assert!(
+                report.contains("<failure message=\"request was successful:\" type=\"Failed\">")
+            );
+            assert!(report.contains("expected: &lt;true&gt;"));
+            assert_eq!(report.matches("<system-out>").count(), 1);
+            assert_eq!(report.matches("<system-err>").count(), 1);
+            assert!(report.contains("<system-out>stdout of line 5\nstdout of line 6</system-out>"));
+        }
+
+        #[test]
+        fn junit_report_writes_exchanges_in_the_same_system_out() {
+            let step = StepStats {
+                attachments: vec![api::HttpExchange {
+                    method: String::from("GET"),
+                    url: String::from("https://api.kraken.com/0/public/Time"),
+                    status: Some(200),
+                    ..api::HttpExchange::default()
+                }],
+                ..failed_step(5, "response is json")
+            };
+            let stats = run(vec![ScenarioStats {
+                steps: vec![step],
+                ..scenario("System time", 4, StatResult::Failed)
+            }]);
+
+            let report = get_junit_report(&stats);
+
+            assert_eq!(report.matches("<system-out>").count(), 1);
+            assert!(report.contains(
+                "<system-out>stdout of line 5\nGET https://api.kraken.com/0/public/Time"
+            ));
+        }
+
+        #[test]
+        fn escape_xml_escapes_markup_characters() {
+            assert_eq!(
+                escape_xml("<a href=\"x\">Tom & Jerry's</a>"),
+                "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+            );
+        }
+
+        #[test]
+        fn junit_report_maps_features_to_suites_and_scenarios_to_testcases() {
+            let stats = RunStats::from_features(vec![
+                feature(
+                    "Orders & trades",
+                    "orders.feature",
+                    vec![
+                        scenario("Open orders", 4, StatResult::Passed),
+                        scenario("Closed orders", 9, StatResult::Skipped),
+                    ],
+                ),
+                feature(
+                    "System time",
+                    "systemtime.feature",
+                    vec![ScenarioStats {
+                        steps: vec![failed_step(5, "response is json")],
+                        ..scenario("System time", 4, StatResult::Failed)
+                    }],
+                ),
+            ]);
+
+            let report = get_junit_report(&stats);
+
+            assert!(report.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+            assert!(report.contains("<testsuites tests=\"3\" failures=\"1\" skipped=\"1\">"));
+            assert!(report.contains(
+                "<testsuite name=\"Orders &amp; trades\" tests=\"2\" failures=\"0\" skipped=\"1\""
+            ));
+            assert_eq!(report.matches("<testcase ").count(), 3);
+            assert_eq!(report.matches("<skipped/>").count(), 1);
+            assert_eq!(report.matches("<failure ").count(), 1);
+            assert!(report.trim_end().ends_with("</testsuites>"));
+        }
+
+        fn parse_feature(source: &str) -> std::rc::Rc<gherkin::Feature> {
+            std::rc::Rc::new(gherkin::Feature::parse(source, Default::default()).unwrap())
+        }
+
+        fn send_scenario<H: EventHandler>(
+            handler: &mut H,
+            feature: &std::rc::Rc<gherkin::Feature>,
+            examples: ExampleValues,
+            step_events: &[StepEvent],
+            result: ScenarioEvent,
+        ) {
+            let scenario = std::rc::Rc::new(feature.scenarios[0].clone());
+            let send = |handler: &mut H, event| {
+                handler.handle_event(&CucumberEvent::Feature(
+                    feature.clone(),
+                    FeatureEvent::Scenario(scenario.clone(), event),
+                ))
+            };
+
+            send(handler, ScenarioEvent::Starting(examples));
+            for (step, event) in scenario.steps.iter().zip(step_events) {
+                let step = std::rc::Rc::new(step.clone());
+                send(
+                    handler,
+                    ScenarioEvent::Step(step.clone(), StepEvent::Starting),
+                );
+                send(handler, ScenarioEvent::Step(step, event.clone()));
+            }
+            send(handler, result);
+        }
+
+        fn handle_scenario(
+            handler: &mut RunEventHandler,
+            step_events: &[StepEvent],
+            result: ScenarioEvent,
+        ) -> RunStats {
+            let feature = parse_feature(
+                "Feature: Orders\n\n  Scenario: Create an order\n    Given an empty cart\n    When the order is placed\n",
+            );
+            handler.handle_event(&CucumberEvent::Feature(
+                feature.clone(),
+                FeatureEvent::Starting,
+            ));
+            send_scenario(
+                handler,
+                &feature,
+                ExampleValues::empty(),
+                step_events,
+                result,
+            );
+            handler.handle_event(&CucumberEvent::Feature(feature, FeatureEvent::Finished));
+
+            let state = handler.state.lock().unwrap();
+            RunStats::from_features(state.features.clone())
+        }
+
+        fn step_results(stats: &RunStats) -> Vec<Option<StatResult>> {
+            stats.features[0].scenarios[0]
+                .steps
+                .iter()
+                .map(|s| s.result.clone())
+                .collect()
+        }
+
+        fn passed() -> StepEvent {
+            StepEvent::Passed(CapturedOutput {
+                out: String::new(),
+                err: String::new(),
+            })
+        }
+
+        #[test]
+        fn unimplemented_step_is_undefined_and_fails_the_run() {
+            let stats = handle_scenario(
+                &mut RunEventHandler::default(),
+                &[passed(), StepEvent::Unimplemented],
+                ScenarioEvent::Skipped,
+            );
+
+            assert!(
+                step_results(&stats) == vec![Some(StatResult::Passed), Some(StatResult::Undefined)]
+            );
+            assert!(stats.features[0].scenarios[0].result == Some(StatResult::Undefined));
+            assert!(stats.failed());
+        }
+
+        #[test]
+        fn skipped_step_is_pending_and_does_not_fail_the_run() {
+            let stats = handle_scenario(
+                &mut RunEventHandler::default(),
+                &[StepEvent::Skipped],
+                ScenarioEvent::Skipped,
+            );
+
+            assert!(
+                step_results(&stats) == vec![Some(StatResult::Pending), Some(StatResult::Skipped)]
+            );
+            assert!(stats.features[0].scenarios[0].result == Some(StatResult::Pending));
+            assert!(!stats.failed());
+        }
+
+        #[test]
+        fn timed_out_step_fails_the_run() {
+            let stats = handle_scenario(
+                &mut RunEventHandler::default(),
+                &[StepEvent::Failed(StepFailureKind::TimedOut)],
+                ScenarioEvent::Failed(FailureKind::TimedOut),
+            );
+
+            assert!(
+                step_results(&stats) == vec![Some(StatResult::TimedOut), Some(StatResult::Skipped)]
+            );
+            assert!(stats.features[0].scenarios[0].result == Some(StatResult::TimedOut));
+            assert!(stats.failed());
+        }
+
+        #[test]
+        fn ambiguous_step_fails_the_run() {
+            let mut handler = RunEventHandler::with_step_patterns(&[
+                (gherkin::StepType::Given, r"^an empty (\w+)$"),
+                (gherkin::StepType::Given, r"^an (\w+) cart$"),
+            ]);
+            let stats = handle_scenario(&mut handler, &[passed(), passed()], ScenarioEvent::Passed);
+
+            assert!(
+                step_results(&stats) == vec![Some(StatResult::Ambiguous), Some(StatResult::Passed)]
+            );
+            assert!(stats.features[0].scenarios[0].result == Some(StatResult::Ambiguous));
+            assert!(stats.failed());
+        }
+
+        #[test]
+        fn passed_scenario_does_not_fail_the_run() {
+            let stats = handle_scenario(
+                &mut RunEventHandler::default(),
+                &[passed(), passed()],
+                ScenarioEvent::Passed,
+            );
+
+            assert!(
+                step_results(&stats) == vec![Some(StatResult::Passed), Some(StatResult::Passed)]
+            );
+            assert!(!stats.failed());
+        }
+
+        #[test]
+        fn outline_rows_are_recorded_as_separate_scenarios() {
+            let feature = parse_feature(concat!(
+                "Feature: Orders\n\n",
+                "  Scenario Outline: Order <count> items\n",
+                "    Given a cart with <count> items\n\n",
+                "    Examples:\n",
+                "      | count |\n",
+                "      | 1     |\n",
+                "      | 2     |\n",
+            ));
+            let keys = vec![String::from("count")];
+            let mut handler = RunEventHandler::default();
+
+            handler.handle_event(&CucumberEvent::Feature(
+                feature.clone(),
+                FeatureEvent::Starting,
+            ));
+            for value in &["1", "2"] {
+                let examples = ExampleValues::new(&keys, &vec![value.to_string()]);
+                send_scenario(
+                    &mut handler,
+                    &feature,
+                    examples,
+                    &[passed()],
+                    ScenarioEvent::Passed,
+                );
+                std::thread::sleep(std::time::Duration::from_millis(2));
+            }
+            handler.handle_event(&CucumberEvent::Feature(feature, FeatureEvent::Finished));
+
+            let state = handler.state.lock().unwrap();
+            let scenarios = &state.features[0].scenarios;
+            assert_eq!(scenarios.len(), 2);
+            assert_eq!(scenarios[0].example_index, Some(0));
+            assert_eq!(scenarios[1].example_index, Some(1));
+            assert!(scenarios.iter().all(|s| s.timing.duration_micros.is_some()));
+            assert!(scenarios[0].timing.end_micros <= scenarios[1].timing.start_micros);
+            assert!(scenarios[0].timing.start_micros < scenarios[1].timing.start_micros);
+        }
+
+        #[test]
+        fn outline_rows_inherit_the_examples_tags() {
+            let feature = parse_feature(concat!(
+                "@orders\n",
+                "Feature: Orders\n\n",
+                "  @outline\n",
+                "  Scenario Outline: Order <count> items\n",
+                "    Given a cart with <count> items\n\n",
+                "    @smoke @orders\n",
+                "    Examples:\n",
+                "      | count |\n",
+                "      | 1     |\n",
+            ));
+            let keys = vec![String::from("count")];
+            let examples = ExampleValues::new(&keys, &vec![String::from("1")]);
+            let stats =
+                ScenarioStats::new(&feature, None, &feature.scenarios[0], Some(0), &examples);
+
+            assert_eq!(stats.tags, vec!["@orders", "@outline", "@smoke"]);
+        }
+
+        #[test]
+        fn markdown_code_is_not_closed_by_backticks_in_its_content() {
+            let stats = run(vec![ScenarioStats {
+                steps: vec![StepStats {
+                    name: String::from("the `status` is ``ok``"),
+                    ..failed_step(5, "expected:\n```\nok\n````")
+                }],
+                ..scenario("Status", 4, StatResult::Failed)
+            }]);
+
+            let report = get_markdown_report(&stats);
+
+            assert!(report.contains("Failing step: ``` Then the `status` is ``ok`` ``` ("));
+            assert!(report.contains("\n`````\nexpected:\n```\nok\n````\n`````\n"));
+            assert_eq!(get_markdown_code("`ok`"), "`` `ok` ``");
+        }
+
+        #[test]
+        fn messages_run_with_an_undefined_step_is_not_successful() {
+            let path =
+                std::env::temp_dir().join(format!("somebdd-messages-{}.ndjson", get_now_micros()));
+            let feature = parse_feature(
+                "Feature: Orders\n\n  Scenario: Create an order\n    Given an empty cart\n",
+            );
+            let mut handler = CucumberMessagesHandler::new(&path.to_string_lossy()).unwrap();
+
+            handler.handle_event(&CucumberEvent::Starting);
+            handler.handle_event(&CucumberEvent::Feature(
+                feature.clone(),
+                FeatureEvent::Starting,
+            ));
+            send_scenario(
+                &mut handler,
+                &feature,
+                ExampleValues::empty(),
+                &[StepEvent::Unimplemented],
+                ScenarioEvent::Skipped,
+            );
+            handler.handle_event(&CucumberEvent::Feature(feature, FeatureEvent::Finished));
+            handler.handle_event(&CucumberEvent::Finished(RunResult {
+                started: std::time::Instant::now(),
+                elapsed: std::time::Duration::default(),
+                features: Default::default(),
+                rules: Default::default(),
+                scenarios: Default::default(),
+                steps: Default::default(),
+            }));
+            drop(handler);
+
+            let content = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let finished: serde_json::Value =
+                serde_json::from_str(content.lines().last().unwrap()).unwrap();
+
+            assert_eq!(finished["testRunFinished"]["success"], false);
+        }
+
+        #[test]
+        fn tap_diagnostics_include_the_exchanges() {
+            let step = StepStats {
+                attachments: vec![api::HttpExchange {
+                    method: String::from("GET"),
+                    url: String::from("https://api.kraken.com/0/public/Time"),
+                    status: Some(200),
+                    response_body: Some(String::from("{\"error\":[]}")),
+                    ..api::HttpExchange::default()
+                }],
+                ..failed_step(5, "response is json")
+            };
+            let stats = run(vec![ScenarioStats {
+                steps: vec![step],
+                ..scenario("System time", 4, StatResult::Failed)
+            }]);
+
+            let report = get_tap_report(&stats);
+
+            assert!(report.contains(concat!(
+                "  exchanges: |\n",
+                "    GET https://api.kraken.com/0/public/Time -> 200\n",
+                "    \n",
+                "    {\"error\":[]}\n",
+                "  ...\n"
+            )));
+        }
+
+        fn timed_step(keyword: &str, name: &str, result: StatResult) -> StepStats {
+            StepStats {
+                name: name.to_string(),
+                keyword: keyword.to_string(),
+                line: 5,
+                result: Some(result),
+                timing: Timing {
+                    start_micros: Some(0),
+                    end_micros: Some(1_500),
+                    duration_micros: Some(1_500),
+                },
+                ..StepStats::default()
+            }
+        }
+
+        #[test]
+        fn escape_csv_quotes_fields_with_separators() {
+            assert_eq!(escape_csv("Open orders"), "Open orders");
+            assert_eq!(escape_csv("orders, trades"), "\"orders, trades\"");
+            assert_eq!(
+                escape_csv("the \"open\" orders"),
+                "\"the \"\"open\"\" orders\""
+            );
+            assert_eq!(escape_csv("first\nsecond"), "\"first\nsecond\"");
+        }
+
+        #[test]
+        fn csv_report_writes_one_row_per_step() {
+            let stats = run(vec![ScenarioStats {
+                steps: vec![
+                    timed_step("Given ", "request is authenticated", StatResult::Passed),
+                    StepStats {
+                        name: String::from("pair is \"XBT, EUR\""),
+                        keyword: String::from("Then "),
+                        line: 6,
+                        ..StepStats::default()
+                    },
+                ],
+                ..scenario("Open orders", 4, StatResult::Failed)
+            }]);
+
+            assert_eq!(
+                get_csv_report(&stats),
+                concat!(
+                    "feature,scenario,keyword,step,status,duration_ms\n",
+                    "Orders,Open orders,Given,request is authenticated,Passed,1.500\n",
+                    "Orders,Open orders,Then,\"pair is \"\"XBT, EUR\"\"\",Skipped,\n",
+                )
+            );
+        }
+
+        #[test]
+        fn tap_report_plans_one_test_point_per_scenario() {
+            let stats = run(vec![
+                scenario("Open orders", 4, StatResult::Passed),
+                scenario("Closed orders", 9, StatResult::Skipped),
+                scenario("Trades #2", 14, StatResult::Pending),
+                ScenarioStats {
+                    steps: vec![failed_step(20, "response is json")],
+                    ..scenario("System time", 19, StatResult::Failed)
+                },
+            ]);
+
+            let report = get_tap_report(&stats);
+            let test_points: Vec<&str> = report
+                .lines()
+                .filter(|l| l.starts_with("ok ") || l.starts_with("not ok "))
+                .collect();
+
+            assert!(report.starts_with("TAP version 13\n1..4\n"));
+            assert_eq!(
+                test_points,
+                vec![
+                    "ok 1 - Orders: Open orders",
+                    "ok 2 - Orders: Closed orders # SKIP",
+                    "not ok 3 - Orders: Trades \\#2 # TODO pending",
+                    "not ok 4 - Orders: System time",
+                ]
+            );
+            assert!(report.contains("  status: Failed\n  location: orders.feature:19\n"));
+            assert!(report.contains("  step: \"Then gets successful response as json\"\n"));
+            assert!(report.contains("  message: |\n    response is json\n"));
+        }
+
+        #[test]
+        fn escape_prometheus_label_escapes_quotes_backslashes_and_newlines() {
+            assert_eq!(
+                escape_prometheus_label("path \"C:\\orders\"\nnext"),
+                "path \\\"C:\\\\orders\\\"\\nnext"
+            );
+        }
+
+        #[test]
+        fn prometheus_report_writes_counts_success_and_step_durations() {
+            let mut stats = run(vec![
+                ScenarioStats {
+                    steps: vec![timed_step("Given ", "pair \"XBT\"", StatResult::Passed)],
+                    ..scenario("Open orders", 4, StatResult::Passed)
+                },
+                scenario("Closed orders", 9, StatResult::Failed),
+            ]);
+            stats.metadata.timing.end_micros = Some(1_500_000);
+
+            let report = get_prometheus_report(&stats);
+
+            assert!(report.contains("somebdd_scenarios{result=\"passed\"} 1\n"));
+            assert!(report.contains("somebdd_scenarios{result=\"failed\"} 1\n"));
+            assert!(report.contains("somebdd_scenarios{result=\"timed_out\"} 0\n"));
+            assert!(report.contains(
+                "somebdd_scenario_success{feature=\"Orders\",scenario=\"Open orders\",line=\"4\"} 1\n"
+            ));
+            assert!(report.contains(
+                "somebdd_scenario_success{feature=\"Orders\",scenario=\"Closed orders\",line=\"9\"} 0\n"
+            ));
+            assert!(report.contains(concat!(
+                "somebdd_step_duration_seconds{feature=\"Orders\",scenario=\"Open orders\",",
+                "line=\"4\",step=\"Given pair \\\"XBT\\\"\",step_line=\"5\"} 0.0015\n"
+            )));
+            assert_eq!(report.matches("somebdd_step_duration_seconds{").count(), 1);
+            assert!(report.ends_with("somebdd_last_run_timestamp_seconds 1.5\n"));
+        }
+    }
+}
 
 pub mod api {
 
+    use crate::state::Timing;
     use hmac::{Hmac, Mac, NewMac};
-    use hyper::{Body, Client, Request, Response, Uri};
+    use hyper::{Body, Client, HeaderMap, Request, Response, Uri};
     use hyper_tls::HttpsConnector;
+    use serde::{Deserialize, Serialize};
     use sha2::Digest;
+    use std::collections::BTreeMap;
     use std::string::FromUtf8Error;
+    use std::sync::{Arc, Mutex};
     use std::time::UNIX_EPOCH;
     use std::{collections::HashMap, time::SystemTime};
 
     pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
     type HmacSha512 = Hmac<crypto_hashes::sha2::Sha512>;
 
+    /// Response bodies longer than this are cut before being recorded.
+    pub const MAX_RECORDED_BODY_BYTES: usize = 4096;
+    const REDACTED_HEADERS: [&str; 2] = ["api-key", "api-sign"];
+
     pub struct ApiContext {
         pub otp: String,
         api_host: String,
         api_key: String,
         secret_key: String,
+        recorder: Option<ExchangeRecorder>,
+    }
+
+    /// An HTTP request and its response, or the error that prevented getting one.
+    #[derive(Default, Clone, Serialize, Deserialize)]
+    pub struct HttpExchange {
+        pub method: String,
+        pub url: String,
+        /// Request headers, with the credentials redacted.
+        pub request_headers: BTreeMap<String, String>,
+        pub status: Option<u16>,
+        pub response_headers: BTreeMap<String, String>,
+        /// Response body, truncated to `MAX_RECORDED_BODY_BYTES`.
+        pub response_body: Option<String>,
+        pub error: Option<String>,
+        pub timing: Timing,
+    }
+
+    /// Collects the exchanges made by `get` and `post` until they are drained, which the
+    /// `RunEventHandler` does whenever a step finishes.
+    #[derive(Default, Clone)]
+    pub struct ExchangeRecorder {
+        exchanges: Arc<Mutex<Vec<HttpExchange>>>,
+    }
+
+    impl ExchangeRecorder {
+        pub fn record(&self, exchange: HttpExchange) {
+            self.exchanges.lock().unwrap().push(exchange);
+        }
+
+        pub fn drain(&self) -> Vec<HttpExchange> {
+            self.exchanges.lock().unwrap().drain(..).collect()
+        }
+    }
+
+    impl std::fmt::Display for HttpExchange {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{} {}", self.method, self.url)?;
+            match (self.status, &self.error) {
+                (Some(status), _) => write!(f, " -> {}", status)?,
+                (None, Some(error)) => write!(f, " -> {}", error)?,
+                (None, None) => {}
+            };
+
+            write!(f, "{}", self.timing)
+        }
+    }
+
+    impl HttpExchange {
+        /// The exchange summary followed by its headers and response body, as reports show it.
+        pub fn get_details(&self) -> String {
+            let mut lines = vec![self.to_string()];
+            self.request_headers
+                .iter()
+                .for_each(|(name, value)| lines.push(format!("> {}: {}", name, value)));
+            self.response_headers
+                .iter()
+                .for_each(|(name, value)| lines.push(format!("< {}: {}", name, value)));
+            if let Some(body) = &self.response_body {
+                lines.push(String::new());
+                lines.push(body.to_owned());
+            }
+
+            lines.join("\n")
+        }
     }
 
     impl ApiContext {
@@ -440,9 +4999,20 @@ pub mod api {
                 api_host,
                 secret_key,
                 otp,
+                recorder: None,
             }
         }
 
+        /// Records every exchange made with this context into `recorder`.
+        pub fn with_recorder(mut self, recorder: ExchangeRecorder) -> Self {
+            self.recorder = Some(recorder);
+            self
+        }
+
+        pub fn get_api_host(&self) -> &str {
+            &self.api_host
+        }
+
         pub fn get_public_api_url(&self) -> String {
             format!("https://{}/0/public/", self.api_host)
         }
@@ -458,19 +5028,27 @@ pub mod api {
     }
 
     pub async fn get(url: &str, params: HashMap<&str, &str>) -> Result<Response<Body>> {
-        let uri = get_url_and_query_string(url, &params);
-        let request = Request::builder()
+        send(get_request(url, &params), None).await
+    }
+
+    /// Same as `get`, recording the exchange when the context has a recorder.
+    pub async fn get_with_context(
+        url: &str,
+        params: HashMap<&str, &str>,
+        api_context: &ApiContext,
+    ) -> Result<Response<Body>> {
+        send(get_request(url, &params), api_context.recorder.as_ref()).await
+    }
+
+    fn get_request(url: &str, params: &HashMap<&str, &str>) -> Request<Body> {
+        let uri = get_url_and_query_string(url, params);
+        Request::builder()
             .uri(uri)
             .method("GET")
             .header("User-Agent", "bdd-awesome-agent/1.0")
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(Body::default())
-            .unwrap();
-
-        let https = HttpsConnector::new();
-        let https_client = Client::builder().build::<_, hyper::Body>(https);
-        let response = https_client.request(request).await?;
-        Ok(response)
+            .unwrap()
     }
 
     pub async fn post(
@@ -498,10 +5076,87 @@ pub mod api {
             .body(Body::from(url_encoded_params))
             .unwrap();
 
+        send(request, api_context.recorder.as_ref()).await
+    }
+
+    async fn send(
+        request: Request<Body>,
+        recorder: Option<&ExchangeRecorder>,
+    ) -> Result<Response<Body>> {
         let https = HttpsConnector::new();
         let https_client = Client::builder().build::<_, hyper::Body>(https);
-        let response = https_client.request(request).await?;
-        Ok(response)
+        let recorder = match recorder {
+            Some(recorder) => recorder,
+            None => return Ok(https_client.request(request).await?),
+        };
+
+        let mut exchange = HttpExchange {
+            method: request.method().to_string(),
+            url: request.uri().to_string(),
+            request_headers: get_recorded_headers(request.headers()),
+            timing: Timing::started(),
+            ..HttpExchange::default()
+        };
+
+        let response = match https_client.request(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                exchange.error = Some(e.to_string());
+                exchange.timing.finish();
+                recorder.record(exchange);
+                return Err(e.into());
+            }
+        };
+
+        // The body is buffered to record it and then handed back untouched to the caller.
+        let (parts, body) = response.into_parts();
+        let body_content = hyper::body::to_bytes(body).await;
+        exchange.timing.finish();
+        exchange.status = Some(parts.status.as_u16());
+        exchange.response_headers = get_recorded_headers(&parts.headers);
+        let body_content = match body_content {
+            Ok(body_content) => body_content,
+            Err(e) => {
+                exchange.error = Some(e.to_string());
+                recorder.record(exchange);
+                return Err(e.into());
+            }
+        };
+        exchange.response_body = Some(get_truncated_body(&body_content));
+        recorder.record(exchange);
+
+        Ok(Response::from_parts(parts, Body::from(body_content)))
+    }
+
+    fn get_recorded_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let value = match REDACTED_HEADERS.contains(&name.as_str()) {
+                    true => String::from("[REDACTED]"),
+                    false => String::from_utf8_lossy(value.as_bytes()).to_string(),
+                };
+                (name.to_string(), value)
+            })
+            .collect()
+    }
+
+    fn get_truncated_body(body: &[u8]) -> String {
+        let content = String::from_utf8_lossy(body);
+        if content.len() <= MAX_RECORDED_BODY_BYTES {
+            return content.to_string();
+        }
+
+        let mut end = MAX_RECORDED_BODY_BYTES;
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        format!(
+            "{}... ({} bytes truncated)",
+            &content[..end],
+            content.len() - end
+        )
     }
 
     pub async fn get_content_as_string(
@@ -544,7 +5199,7 @@ pub mod api {
     ) -> String {
         let sha256 = sha2::Sha256::digest((nonce.to_string() + url_encoded_params).as_bytes());
         let mut sha512_params: Vec<u8> = Vec::from(uri_path.as_bytes());
-        sha512_params.extend_from_slice(sha256.as_slice());
+        sha512_params.extend_from_slice(&sha256);
 
         let secret_key_bytes = base64::decode(secret_key).unwrap();
         let mut mac = HmacSha512::new_from_slice(&secret_key_bytes).unwrap();
@@ -1,9 +1,11 @@
-use cucumber_rust::{async_trait, t, Context, Cucumber, World};
+use cucumber_rust::output::BasicOutput;
+use cucumber_rust::{async_trait, gherkin::StepType, t, Context, Cucumber, World};
 use hyper::{Body, Response};
 use serde_json::Value;
-use somebdd::api::ApiContext;
+use somebdd::api::{ApiContext, ExchangeRecorder};
 use somebdd::state::{
-    print_test_results, write_result_file, EventHandlerState, RunEventHandler, RunStats,
+    ColorMode, CompositeEventHandler, ConsoleWriter, CucumberMessagesHandler, EventHandlerState,
+    EventStreamHandler, OutputFormat, ReportWriter, RunEventHandler, RunMetadata, RunStats,
 };
 use std::convert::Infallible;
 use std::env;
@@ -20,7 +22,7 @@ mod test_steps {
     use crate::MyWorld;
     use cucumber_rust::Steps;
     use serde_json::Value;
-    use somebdd::api::{get, get_content_as_string, post, ApiContext};
+    use somebdd::api::{get_content_as_string, get_with_context, post, ApiContext};
     use spectral::{self, asserting, boolean::BooleanAssertions};
     use std::collections::HashMap;
 
@@ -47,33 +49,67 @@ mod test_steps {
         }
     }
 
+    // Step texts shared by the definitions in `steps` and the lists handed to the stats
+    // handler, so that both always describe the same steps.
+    const NOT_AUTHENTICATED: &str = "request is not authenticated";
+    const AUTHENTICATED: &str = "request is authenticated";
+    const SYSTEM_TIME_REQUESTED: &str = "system time is requested";
+    const OPEN_ORDERS_REQUESTED: &str = "all current open orders are requested";
+    const SUCCESSFUL_JSON_RESPONSE: &str = "gets successful response as json";
+    const EMPTY_ERROR_LIST: &str = "response contains error list as empty";
+    const EMPTY_ORDER_LIST: &str = "response contains order list as empty";
+    const ASSET_PAIR_REQUESTED: &str = "asset pair information is requested for (.*) and (.*)";
+    const ONLY_ASSET_PAIR: &str = "response only contains asset pair information (.*)";
+    const ASSET_PAIR_AS_EXPECTED: &str =
+        "asset pair information for (.*) and (.*) as (.*) is as expected";
+
+    /// Literal step definitions of `steps`. cucumber_rust resolves these before any regex
+    /// definition, so they are never reported as ambiguous.
+    pub fn step_literals() -> Vec<(StepType, &'static str)> {
+        vec![
+            (StepType::Given, NOT_AUTHENTICATED),
+            (StepType::Given, AUTHENTICATED),
+            (StepType::When, SYSTEM_TIME_REQUESTED),
+            (StepType::When, OPEN_ORDERS_REQUESTED),
+            (StepType::Then, SUCCESSFUL_JSON_RESPONSE),
+            (StepType::Then, EMPTY_ERROR_LIST),
+            (StepType::Then, EMPTY_ORDER_LIST),
+        ]
+    }
+
+    /// Regex step definitions of `steps`, used to report steps matching more than one of
+    /// them as ambiguous. cucumber_rust doesn't expose its definitions, so every regex step
+    /// added to `steps` must be listed here too.
+    pub fn step_patterns() -> Vec<(StepType, &'static str)> {
+        vec![
+            (StepType::When, ASSET_PAIR_REQUESTED),
+            (StepType::Then, ONLY_ASSET_PAIR),
+            (StepType::Then, ASSET_PAIR_AS_EXPECTED),
+        ]
+    }
+
     pub fn steps() -> Steps<MyWorld> {
         let mut builder: Steps<MyWorld> = Steps::new();
 
-        builder.given(
-            "request is not authenticated",
-            |mut world: crate::MyWorld, _ctx| {
-                let api_context = _ctx.get::<ApiContext>().unwrap();
-                world.base_url = Some(api_context.get_public_api_url());
-                world
-            },
-        );
+        builder.given(NOT_AUTHENTICATED, |mut world: crate::MyWorld, _ctx| {
+            let api_context = _ctx.get::<ApiContext>().unwrap();
+            world.base_url = Some(api_context.get_public_api_url());
+            world
+        });
 
-        builder.given(
-            "request is authenticated",
-            |mut world: crate::MyWorld, _ctx| {
-                let api_context = _ctx.get::<ApiContext>().unwrap();
-                world.base_url = Some(api_context.get_private_api_url());
-                world
-            },
-        );
+        builder.given(AUTHENTICATED, |mut world: crate::MyWorld, _ctx| {
+            let api_context = _ctx.get::<ApiContext>().unwrap();
+            world.base_url = Some(api_context.get_private_api_url());
+            world
+        });
 
         builder.when_async(
-            "system time is requested",
+            SYSTEM_TIME_REQUESTED,
             t!(|mut world: crate::MyWorld, _ctx| {
                 world.set_url_with_path("Time");
+                let api_context = _ctx.get::<ApiContext>().unwrap();
                 let url = &world.url.take().unwrap();
-                let response = get(url, HashMap::default()).await;
+                let response = get_with_context(url, HashMap::default(), api_context).await;
                 if response.is_ok() {
                     world.last_response = Some(Box::new(response.unwrap()))
                 }
@@ -82,14 +118,15 @@ mod test_steps {
         );
 
         builder.when_regex_async(
-            "asset pair information is requested for (.*) and (.*)",
+            ASSET_PAIR_REQUESTED,
             t!(|mut world: crate::MyWorld, _ctx| {
                 world.set_url_with_path("AssetPairs");
                 let pair = _ctx.matches[1].to_owned() + &_ctx.matches[2].to_owned();
                 let mut params: HashMap<&str, &str> = HashMap::default();
                 params.insert("pair", &pair);
+                let api_context = _ctx.get::<ApiContext>().unwrap();
                 let url = &world.url.take().unwrap();
-                let response = get(url, params).await;
+                let response = get_with_context(url, params, api_context).await;
                 if response.is_ok() {
                     world.last_response = Some(Box::new(response.unwrap()))
                 }
@@ -98,7 +135,7 @@ mod test_steps {
         );
 
         builder.when_async(
-            "all current open orders are requested",
+            OPEN_ORDERS_REQUESTED,
             t!(|mut world: crate::MyWorld, _ctx| {
                 let mut params: HashMap<&str, &str> = HashMap::default();
                 let api_context = _ctx.get::<ApiContext>().unwrap();
@@ -115,7 +152,7 @@ mod test_steps {
         );
 
         builder.then_async(
-            "gets successful response as json",
+            SUCCESSFUL_JSON_RESPONSE,
             t!(|mut world: crate::MyWorld, _ctx| {
                 asserting(&"request was successful")
                     .that(&world.last_response.is_some())
@@ -139,140 +176,126 @@ mod test_steps {
             }),
         );
 
-        builder.then(
-            "response contains error list as empty",
-            |world: crate::MyWorld, _ctx| {
-                let content = world.last_content_response.clone().unwrap();
-                let errors = content["error"]
-                    .as_array()
-                    .expect("Impossible to get error property as array");
-                asserting(&"error property is empty")
-                    .that(&errors.len())
-                    .is_equal_to(0);
-                world
-            },
-        );
+        builder.then(EMPTY_ERROR_LIST, |world: crate::MyWorld, _ctx| {
+            let content = world.last_content_response.clone().unwrap();
+            let errors = content["error"]
+                .as_array()
+                .expect("Impossible to get error property as array");
+            asserting(&"error property is empty")
+                .that(&errors.len())
+                .is_equal_to(0);
+            world
+        });
+
+        builder.then(EMPTY_ORDER_LIST, |world: crate::MyWorld, _ctx| {
+            let content = world.last_content_response.clone().unwrap();
+            let result = content["result"].as_object().unwrap();
+            let open_orders = result.get("open").unwrap().as_object().unwrap();
+            asserting(&"open orders list is empty")
+                .that(&open_orders.keys().len())
+                .is_equal_to(0);
+            world
+        });
+
+        builder.then_regex(ONLY_ASSET_PAIR, |world: crate::MyWorld, _ctx| {
+            let content = world.last_content_response.clone().unwrap();
+            let property_name = _ctx.matches[1].to_owned();
+            let result = content["result"]
+                .as_object()
+                .expect("Impossible to get result object from response");
+            result
+                .get(&property_name)
+                .expect(format!("Impossible to get property '{}'", property_name).as_str());
+            asserting(&"result only contains one property")
+                .that(&result.len())
+                .is_equal_to(1);
+            world
+        });
+
+        builder.then_regex(ASSET_PAIR_AS_EXPECTED, |world: crate::MyWorld, _ctx| {
+            let first_currency = _ctx.matches[1].to_owned();
+            let second_currency = _ctx.matches[2].to_owned();
+            let pair_id = _ctx.matches[3].to_owned();
+            let content = world.last_content_response.clone().unwrap();
+            let result = content["result"].as_object().unwrap();
+            let pair = result.get(&pair_id).unwrap().as_object().unwrap();
+
+            let expected_string_properties = vec![
+                "altname",
+                "wsname",
+                "aclass_base",
+                "base",
+                "aclass_quote",
+                "quote",
+                "lot",
+                "fee_volume_currency",
+                "ordermin",
+            ];
+
+            let expected_numeric_properties = vec![
+                "pair_decimals",
+                "lot_decimals",
+                "lot_multiplier",
+                "margin_call",
+                "margin_stop",
+            ];
+
+            let expected_array_properties =
+                vec!["leverage_buy", "leverage_sell", "fees", "fees_maker"];
+
+            let expected_properties = [
+                expected_string_properties.clone(),
+                expected_numeric_properties.clone(),
+                expected_array_properties.clone(),
+            ]
+            .concat();
+
+            expected_properties.iter().for_each(|property| {
+                asserting(format!("contains property {}", property).as_str())
+                    .that(&pair.contains_key(*property))
+                    .is_true();
+            });
 
-        builder.then(
-            "response contains order list as empty",
-            |world: crate::MyWorld, _ctx| {
-                let content = world.last_content_response.clone().unwrap();
-                let result = content["result"].as_object().unwrap();
-                let open_orders = result.get("open").unwrap().as_object().unwrap();
-                asserting(&"open orders list is empty")
-                    .that(&open_orders.keys().len())
-                    .is_equal_to(0);
-                world
-            },
-        );
+            expected_string_properties.iter().for_each(|property| {
+                asserting(format!("property {} value is string type", property).as_str())
+                    .that(&pair.get(*property).unwrap().is_string())
+                    .is_true();
+            });
 
-        builder.then_regex(
-            "response only contains asset pair information (.*)",
-            |world: crate::MyWorld, _ctx| {
-                let content = world.last_content_response.clone().unwrap();
-                let property_name = _ctx.matches[1].to_owned();
-                let result = content["result"]
-                    .as_object()
-                    .expect("Impossible to get result object from response");
-                result
-                    .get(&property_name)
-                    .expect(format!("Impossible to get property '{}'", property_name).as_str());
-                asserting(&"result only contains one property")
-                    .that(&result.len())
-                    .is_equal_to(1);
-                world
-            },
-        );
+            expected_numeric_properties.iter().for_each(|property| {
+                asserting(format!("property {} value is numeric type", property).as_str())
+                    .that(&pair.get(*property).unwrap().is_number())
+                    .is_true();
+            });
 
-        builder.then_regex(
-            "asset pair information for (.*) and (.*) as (.*) is as expected",
-            |world: crate::MyWorld, _ctx| {
-                let first_currency = _ctx.matches[1].to_owned();
-                let second_currency = _ctx.matches[2].to_owned();
-                let pair_id = _ctx.matches[3].to_owned();
-                let content = world.last_content_response.clone().unwrap();
-                let result = content["result"].as_object().unwrap();
-                let pair = result.get(&pair_id).unwrap().as_object().unwrap();
-
-                let expected_string_properties = vec![
-                    "altname",
-                    "wsname",
-                    "aclass_base",
-                    "base",
-                    "aclass_quote",
-                    "quote",
-                    "lot",
-                    "fee_volume_currency",
-                    "ordermin",
-                ];
-
-                let expected_numeric_properties = vec![
-                    "pair_decimals",
-                    "lot_decimals",
-                    "lot_multiplier",
-                    "margin_call",
-                    "margin_stop",
-                ];
-
-                let expected_array_properties =
-                    vec!["leverage_buy", "leverage_sell", "fees", "fees_maker"];
-
-                let expected_properties = [
-                    expected_string_properties.clone(),
-                    expected_numeric_properties.clone(),
-                    expected_array_properties.clone(),
-                ]
-                .concat();
-
-                expected_properties.iter().for_each(|property| {
-                    asserting(format!("contains property {}", property).as_str())
-                        .that(&pair.contains_key(*property))
-                        .is_true();
-                });
-
-                expected_string_properties.iter().for_each(|property| {
-                    asserting(format!("property {} value is string type", property).as_str())
-                        .that(&pair.get(*property).unwrap().is_string())
-                        .is_true();
-                });
-
-                expected_numeric_properties.iter().for_each(|property| {
-                    asserting(format!("property {} value is numeric type", property).as_str())
-                        .that(&pair.get(*property).unwrap().is_number())
-                        .is_true();
-                });
-
-                expected_array_properties.iter().for_each(|property| {
-                    asserting(format!("property {} value is array type", property).as_str())
-                        .that(&pair.get(*property).unwrap().is_array())
-                        .is_true();
-                });
-
-                asserting(&"altname contains the expected value")
-                    .that(&pair.get("altname").unwrap().as_str().unwrap())
-                    .is_equal_to((first_currency.to_owned() + &second_currency).as_str());
-                asserting(&"wsname contains the expected value")
-                    .that(&pair.get("wsname").unwrap().as_str().unwrap())
-                    .is_equal_to(format!("{}/{}", first_currency, second_currency).as_str());
+            expected_array_properties.iter().for_each(|property| {
+                asserting(format!("property {} value is array type", property).as_str())
+                    .that(&pair.get(*property).unwrap().is_array())
+                    .is_true();
+            });
 
-                world
-            },
-        );
+            asserting(&"altname contains the expected value")
+                .that(&pair.get("altname").unwrap().as_str().unwrap())
+                .is_equal_to((first_currency.to_owned() + &second_currency).as_str());
+            asserting(&"wsname contains the expected value")
+                .that(&pair.get("wsname").unwrap().as_str().unwrap())
+                .is_equal_to(format!("{}/{}", first_currency, second_currency).as_str());
+
+            world
+        });
 
         builder
     }
 }
 
+const FEATURE_PATHS: [&str; 1] = ["./features"];
+
 #[tokio::main]
 async fn main() {
-    let set_and_run_world = |world: Cucumber<MyWorld>,
-                             api_host: String,
-                             api_key: String,
-                             secret_key: String,
-                             otp: String| {
+    let set_and_run_world = |world: Cucumber<MyWorld>, api_context: ApiContext| {
         world
-            .context(Context::new().add(ApiContext::new(api_key, api_host, secret_key, otp)))
-            .features(&["./features"])
+            .context(Context::new().add(api_context))
+            .features(&FEATURE_PATHS)
             .steps(test_steps::steps())
             .enable_capture(true)
     };
@@ -298,25 +321,99 @@ async fn main() {
         _ => panic!("You must provide the otp as fourth parameter"),
     };
 
-    match params.get(4) {
-        Some(filename) => {
-            let event_handler = RunEventHandler::default();
-            let world = Cucumber::with_handler(event_handler.clone());
-            let result = set_and_run_world(world, host, api_key, secret_key, otp)
-                .run()
-                .await;
-            let state: EventHandlerState = event_handler.state.lock().unwrap().clone();
-            let stats = RunStats::new(&result, &state);
-            print_test_results(&stats);
-            write_result_file(filename, &stats);
-            let code = if result.failed() { 1 } else { 0 };
-            std::process::exit(code);
+    let mut reports = Vec::new();
+    let mut events = None;
+    let mut messages = None;
+    let mut color = ColorMode::Auto;
+    let mut pretty = false;
+    let mut options = params.iter().skip(4);
+    // The report used to be given as a bare fifth parameter, written to the ./out/ directory.
+    if let Some(output) = params.get(4).filter(|o| !o.starts_with("--")) {
+        let (format, filename) = OutputFormat::from_output(output);
+        reports.push((format, format!("./out/{}", filename)));
+        options.next();
+    }
+
+    while let Some(option) = options.next() {
+        let mut get_value = || match options.next() {
+            Some(v) => v.to_owned(),
+            _ => panic!("You must provide a value for {}", option),
+        };
+
+        match option.as_str() {
+            "--report" => reports.push(OutputFormat::from_output(&get_value())),
+            "--pretty" => pretty = true,
+            "--events" => events = Some(get_value()),
+            "--messages" => messages = Some(get_value()),
+            "--color" => {
+                color = get_value()
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Invalid value for --color: {}", e))
+            }
+            _ => panic!("Unknown option {}", option),
         }
-        _ => {
-            let world = Cucumber::<MyWorld>::new();
-            set_and_run_world(world, host, api_key, secret_key, otp)
-                .run_and_exit()
-                .await;
+    }
+
+    let reports: Vec<Box<dyn ReportWriter>> = reports
+        .iter()
+        .map(|(format, path)| format.writer(path, pretty))
+        .collect();
+
+    // A stream written to stdout would be mixed with the console output, so the console
+    // output is left out.
+    let console_output = events.as_deref() != Some("-") && messages.as_deref() != Some("-");
+    let mut event_handler = CompositeEventHandler::default();
+    if console_output {
+        event_handler.add(BasicOutput::new(false));
+    }
+
+    let recorder = ExchangeRecorder::default();
+    let stats_handler = RunEventHandler::with_step_patterns(&test_steps::step_patterns())
+        .with_step_literals(&test_steps::step_literals())
+        .with_exchange_recorder(recorder.clone());
+    if !reports.is_empty() {
+        event_handler.add(stats_handler.clone());
+    }
+
+    if let Some(destination) = events {
+        let stream_handler = EventStreamHandler::new(&destination)
+            .unwrap_or_else(|e| panic!("Unable to open the event stream: {}", e));
+        event_handler.add(stream_handler);
+    }
+
+    if let Some(destination) = messages {
+        let messages_handler = CucumberMessagesHandler::new(&destination)
+            .unwrap_or_else(|e| panic!("Unable to open the messages stream: {}", e));
+        event_handler.add(messages_handler);
+    }
+
+    let mut api_context = ApiContext::new(api_key, host, secret_key, otp);
+    if !reports.is_empty() {
+        api_context = api_context.with_recorder(recorder);
+    }
+    // cucumber_rust doesn't filter scenarios by tag, so every run records no tag filters.
+    let metadata = RunMetadata::new(&api_context, &FEATURE_PATHS, &[]);
+    let world = Cucumber::with_handler(event_handler);
+    let result = set_and_run_world(world, api_context).run().await;
+    let mut code = if result.failed() { 1 } else { 0 };
+
+    if !reports.is_empty() {
+        let state: EventHandlerState = stats_handler.state.lock().unwrap().clone();
+        let stats = RunStats::new(&result, &state, metadata);
+        if stats.failed() {
+            code = 1;
         }
-    };
+
+        if console_output {
+            let _ = ConsoleWriter { color }.write(&stats);
+        }
+        for report in reports {
+            if let Err(e) = report.write(&stats) {
+                eprintln!("{}", e);
+                code = 1;
+            }
+        }
+    }
+
+    std::process::exit(code);
 }